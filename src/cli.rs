@@ -0,0 +1,52 @@
+//! Definition of Eddie's command line interface. Running `eddie` without any subcommand
+//! starts the UI.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(version, about = "A command manager you can use from your terminal!")]
+pub struct Cli {
+    /// Read the config from this directory (or single file) instead of searching for it.
+    /// Takes precedence over $EDDIE_CONFIG_DIR
+    #[clap(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    #[clap(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Load the config and report any problem found in it, together with where the config was
+    /// searched for. Exits with a non-zero status if there are errors
+    Check,
+    /// Print the JSON Schema of Eddie's config files, to be used by editors for
+    /// autocompletion and validation
+    Schema,
+    /// Print the tree of groups and commands, or only the part of it under a group
+    List {
+        /// The key path of the group (e.g. `work.general`), or the names of the groups leading
+        /// to it, separated by `/` (e.g. `Work/General`)
+        path: Option<String>,
+        /// Print the tree as JSON: every node has its `key_path`, `name`, `description`,
+        /// `command`, `external` flag and `children`
+        #[clap(long)]
+        json: bool,
+    },
+    /// Run a command of the config without starting the UI, and exit with its exit code
+    Run {
+        /// The key path of the command (e.g. `work.general.echo_hi`), or the names of the
+        /// groups leading to it and its own, separated by `/` (e.g. `Work/General/Echo hi`)
+        path: String,
+        /// The value of one of the command's arguments. The others take their default value
+        #[clap(long = "arg", short = 'a', value_name = "NAME=VALUE")]
+        args: Vec<String>,
+        /// Don't ask to confirm commands which set `confirm`
+        #[clap(long, short = 'y')]
+        yes: bool,
+        /// Arguments appended to the command
+        #[clap(last = true, value_name = "ARGS")]
+        extra_args: Vec<String>,
+    },
+}
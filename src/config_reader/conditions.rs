@@ -0,0 +1,132 @@
+//! The conditions module evaluates the `when` table of the nodes ([WhenSchema]), which
+//! decides whether a node is available on the current machine. Conditions are evaluated
+//! once, when the config is loaded.
+
+use std::env;
+use std::path::Path;
+
+use crate::config_reader::config_checker::find_on_path;
+use crate::config_reader::config_schema::WhenSchema;
+use crate::config_reader::expansion;
+
+/// Returns why the node with the conditions `when` is not available on this machine, or
+/// `None` if all the conditions are met. Relative paths in `file_exists` are relative to
+/// `base_dir`.
+pub fn unmet_condition(when: WhenSchema, base_dir: Option<&Path>) -> Option<String> {
+    if let Some(os) = when.os.map(|e| e.into_vec()) {
+        if !os.iter().any(|e| e == env::consts::OS) {
+            return Some(format!(
+                "only available on {} (this is {})",
+                os.join(", "),
+                env::consts::OS
+            ));
+        }
+    }
+
+    if let Some(patterns) = when.hostname.map(|e| e.into_vec()) {
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+        let matches = patterns.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .map(|e| e.matches(&hostname))
+                .unwrap_or(false)
+        });
+
+        if !matches {
+            return Some(format!(
+                "only available on the hosts {} (this is {})",
+                patterns.join(", "),
+                hostname
+            ));
+        }
+    }
+
+    for name in when.env_set.map(|e| e.into_vec()).unwrap_or_default() {
+        if env::var_os(&name).is_none() {
+            return Some(format!("the environment variable `{}` is not set", name));
+        }
+    }
+
+    for file in when.file_exists.map(|e| e.into_vec()).unwrap_or_default() {
+        let path = expansion::expand_path(&file, base_dir);
+        if !path.exists() {
+            return Some(format!("{} doesn't exist", path.display()));
+        }
+    }
+
+    for program in when.on_path.map(|e| e.into_vec()).unwrap_or_default() {
+        if find_on_path(&program).is_none() {
+            return Some(format!("`{}` is not on PATH", program));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config_reader::config_schema::OneOrMany;
+
+    use super::*;
+
+    fn one(value: &str) -> Option<OneOrMany> {
+        Some(OneOrMany::One(value.to_string()))
+    }
+
+    #[test]
+    fn test_met_conditions() {
+        let when = WhenSchema {
+            os: Some(OneOrMany::Many(vec![
+                "plan9".to_string(),
+                env::consts::OS.to_string(),
+            ])),
+            hostname: one("*"),
+            env_set: one("PATH"),
+            file_exists: one("."),
+            on_path: one("sh"),
+        };
+
+        assert_eq!(unmet_condition(when, Some(Path::new("/"))), None);
+    }
+
+    #[test]
+    fn test_unmet_conditions() {
+        let unmet = |when| unmet_condition(when, Some(Path::new("/surely/not")));
+
+        assert_eq!(
+            unmet(WhenSchema {
+                os: one("plan9"),
+                ..Default::default()
+            }),
+            Some(format!(
+                "only available on plan9 (this is {})",
+                env::consts::OS
+            ))
+        );
+        assert_eq!(
+            unmet(WhenSchema {
+                env_set: one("EDDIE_SURELY_NOT_SET"),
+                ..Default::default()
+            }),
+            Some("the environment variable `EDDIE_SURELY_NOT_SET` is not set".to_string())
+        );
+        assert_eq!(
+            unmet(WhenSchema {
+                file_exists: one("real"),
+                ..Default::default()
+            }),
+            Some("/surely/not/real doesn't exist".to_string())
+        );
+        assert_eq!(
+            unmet(WhenSchema {
+                on_path: one("surely-not-a-real-program"),
+                ..Default::default()
+            }),
+            Some("`surely-not-a-real-program` is not on PATH".to_string())
+        );
+        assert!(unmet(WhenSchema {
+            hostname: one("surely-not-*-this-host"),
+            ..Default::default()
+        })
+        .is_some());
+    }
+}
@@ -1,171 +1,429 @@
 //! The config_aggregator module is pretty simple. It exposes functionality to get
-//! the appropriate configuration directory ([get_proper_config_directory]), walking
-//! the directory and finding the TOML files ([get_list_of_toml_files_in_dir]), and
-//! actually reading TOML files into a string ([read_file_contents]).
+//! the appropriate configuration directory ([get_proper_config_directory], searched in the
+//! order given by [config_search_order]), walking
+//! the directory and finding the config files ([get_list_of_config_files_in_dir]), and
+//! actually reading them into a string ([read_file_contents]). Config files can be written
+//! in any of the formats of [ConfigFormat].
 //!
-//! The main entry point to the module is [get_aggregated_tomls] which basically just
-//! reads all the config TOMLs and returns a huge string which is the aggregated
-//! content of all the TOMLs.
+//! Note that this module doesn't parse the files, it only finds and reads them. Merging
+//! their contents is the job of [crate::config_reader::config_merger].
+//!
+//! On top of the global config directory, the module can also discover _project_
+//! configurations ([get_project_configs]) by walking up from the current working
+//! directory looking for `.eddie/` directories or `eddie.toml` files.
 
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
 use walkdir::WalkDir;
 
-/// This method will try to "guess" which is the appropriate directory in which to
-/// search for Eddie's TOML files. The method will check the following directories and
-/// return the one it finds first. They're checked in the following order:
-/// 1. `~/.config/eddie/`
+use crate::config_reader::config_format::ConfigFormat;
+
+/// The extensions that a project file (`eddie.toml`, see [get_project_configs]) can have.
+const PROJECT_FILE_EXTENSIONS: [&str; 4] = ["toml", "yaml", "yml", "json"];
+
+/// The environment variable which sets the global config directory (or file), see
+/// [config_search_order].
+pub const CONFIG_DIR_VAR: &str = "EDDIE_CONFIG_DIR";
+
+/// A place where the global config may be, see [config_search_order].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigCandidate {
+    /// A directory of config files, or a single config file.
+    pub path: PathBuf,
+    /// Why the place is searched, e.g. `--config` or `$XDG_CONFIG_HOME`.
+    pub origin: &'static str,
+}
+
+impl fmt::Display for ConfigCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.path.display(), self.origin)
+    }
+}
+
+/// Returns the places where the global config is searched for, in order. `config_path`
+/// (given with `--config`) or else `$EDDIE_CONFIG_DIR` are the only place searched if they
+/// are set. Otherwise these are searched:
+/// 1. `$XDG_CONFIG_HOME/eddie/` (`~/.config/eddie/` if it isn't set)
 /// 2. `~/.eddie/`
-/// 1. `/etc/eddie/`
-pub fn get_proper_config_directory() -> PathBuf {
-    let home_dir_path = dirs::home_dir().unwrap();
-    let home_dir = home_dir_path.to_str().unwrap();
-
-    let config_dirs = vec![
-        format!("{}/.config/eddie/", home_dir),
-        format!("{}/.eddie/", home_dir),
-        "/etc/eddie/".to_string(),
-    ];
-
-    let existing_dirs: Vec<_> = config_dirs
+/// 3. `eddie/` in every directory of `$XDG_CONFIG_DIRS` (`/etc/xdg/` if it isn't set)
+/// 4. `/etc/eddie/`
+pub fn config_search_order(config_path: Option<&Path>) -> Vec<ConfigCandidate> {
+    let var = |name: &str| env::var_os(name).filter(|e| !e.is_empty());
+
+    search_order(config_path, var, dirs::home_dir())
+}
+
+fn search_order(
+    config_path: Option<&Path>,
+    var: impl Fn(&str) -> Option<std::ffi::OsString>,
+    home_dir: Option<PathBuf>,
+) -> Vec<ConfigCandidate> {
+    let candidate = |path: PathBuf, origin| ConfigCandidate { path, origin };
+
+    if let Some(path) = config_path {
+        return vec![candidate(path.to_path_buf(), "--config")];
+    }
+    if let Some(path) = var(CONFIG_DIR_VAR) {
+        return vec![candidate(PathBuf::from(path), "$EDDIE_CONFIG_DIR")];
+    }
+
+    let mut candidates = vec![];
+
+    // the XDG spec says that relative paths in its variables must be ignored
+    match var("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => {
+            candidates.push(candidate(dir.join("eddie"), "$XDG_CONFIG_HOME"))
+        }
+        _ => candidates.extend(
+            home_dir
+                .iter()
+                .map(|e| candidate(e.join(".config/eddie"), "default $XDG_CONFIG_HOME")),
+        ),
+    }
+
+    candidates.extend(
+        home_dir
+            .iter()
+            .map(|e| candidate(e.join(".eddie"), "home directory")),
+    );
+
+    match var("XDG_CONFIG_DIRS") {
+        Some(dirs) => candidates.extend(
+            env::split_paths(&dirs)
+                .filter(|e| e.is_absolute())
+                .map(|e| candidate(e.join("eddie"), "$XDG_CONFIG_DIRS")),
+        ),
+        None => candidates.push(candidate(
+            PathBuf::from("/etc/xdg/eddie"),
+            "default $XDG_CONFIG_DIRS",
+        )),
+    }
+
+    candidates.push(candidate(PathBuf::from("/etc/eddie"), "system"));
+
+    candidates
+}
+
+/// Returns the first place of [config_search_order] (for `config_path`) which exists, which
+/// is either a directory of config files or a single config file. If none of them exists
+/// then the places which were searched are returned instead.
+pub fn get_proper_config_directory(
+    config_path: Option<&Path>,
+) -> Result<PathBuf, Vec<ConfigCandidate>> {
+    let candidates = config_search_order(config_path);
+
+    candidates
         .iter()
-        .map(|e| Path::new(e))
-        .filter(|&e| e.exists())
+        .find_map(|e| e.path.canonicalize().ok())
+        .ok_or(candidates)
+}
+
+/// A project configuration found while walking up the directory tree (see
+/// [get_project_configs]).
+#[derive(Debug, PartialEq)]
+pub struct ProjectConfig {
+    /// The directory that contains the `.eddie/` directory or the `eddie.toml` file.
+    pub root: PathBuf,
+    /// All the config files that make up the configuration of the project.
+    pub files: Vec<PathBuf>,
+}
+
+/// This method will walk up from `start_dir` to the root of the filesystem and collect
+/// every directory that contains either a `.eddie/` directory or an `eddie.toml` file.
+/// Global config directories (see [config_search_order]) and `config_dir`, the one in use,
+/// are never considered to be project configurations, even if they're found along the way.
+///
+/// The returned list is ordered from the innermost project (the one closest to
+/// `start_dir`) to the outermost one.
+pub fn get_project_configs(start_dir: &Path, config_dir: &Path) -> Vec<ProjectConfig> {
+    let global_dirs: Vec<PathBuf> = config_search_order(None)
+        .iter()
+        .map(|e| e.path.as_path())
+        .chain(std::iter::once(config_dir))
+        .filter_map(|e| e.canonicalize().ok())
         .collect();
 
-    existing_dirs
-        .get(0)
-        .expect("There is no usable config directories")
-        .canonicalize()
-        .unwrap()
+    find_project_configs(start_dir, &global_dirs)
+}
+
+fn find_project_configs(start_dir: &Path, ignored_dirs: &[PathBuf]) -> Vec<ProjectConfig> {
+    let mut projects = vec![];
+
+    for dir in start_dir.ancestors() {
+        let mut files = vec![];
+
+        let project_dir = dir.join(".eddie");
+        if project_dir.is_dir() {
+            let is_global = match project_dir.canonicalize() {
+                Ok(p) => ignored_dirs.contains(&p),
+                Err(_) => true,
+            };
+
+            if !is_global {
+                files.extend(get_list_of_config_files_in_dir(project_dir));
+            }
+        }
+
+        for extension in PROJECT_FILE_EXTENSIONS {
+            let project_file = dir.join("eddie").with_extension(extension);
+            if project_file.is_file() {
+                files.push(project_file);
+            }
+        }
+
+        if !files.is_empty() {
+            files.sort();
+            projects.push(ProjectConfig {
+                root: dir.to_path_buf(),
+                files,
+            });
+        }
+    }
+
+    projects
 }
 
 /// This method will walk the provided [PathBuf] recursively (using [WalkDir]), and will
-/// return a list of all the paths at which it found a file with a `.toml` extension.
-fn get_list_of_toml_files_in_dir(ddir: PathBuf) -> Vec<PathBuf> {
+/// return a list of all the paths at which it found a config file (see
+/// [ConfigFormat::from_path]).
+fn get_list_of_config_files_in_dir(ddir: PathBuf) -> Vec<PathBuf> {
     WalkDir::new(ddir)
         .follow_links(true)
         .into_iter()
         .filter_map(|x| x.ok())
         .map(|x| x.into_path())
-        .filter(|x| {
-            if let Some(ext) = x.extension() {
-                ext == "toml"
-            } else {
-                false
-            }
-        })
+        .filter(|x| is_config_file(x))
         .collect()
 }
 
-/// This method will try to read the contents of the file at the specified [PathBuf] and will
-/// panic if it is not able to read said file's contents.
-fn read_file_contents(file_path: &PathBuf) -> String {
-    fs::read_to_string(file_path).expect("Something went wrong when reading the file")
+/// Whether the file at `path` is a config file. JSON Schemas (`*.schema.json`, see
+/// [crate::config_reader::config_schema]) are kept next to the config files for editors, but
+/// they're not config files themselves.
+fn is_config_file(path: &Path) -> bool {
+    let is_schema = path
+        .file_name()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.ends_with(".schema.json"));
+
+    ConfigFormat::from_path(path).is_some() && !is_schema
+}
+
+/// This method will try to read the contents of the file at the specified [PathBuf].
+pub fn read_file_contents(file_path: &Path) -> io::Result<String> {
+    fs::read_to_string(file_path)
 }
 
-/// This method will read all of the toml files in Eddie's config directory (see
-/// [get_proper_config_directory]) and return a huge string which is the aggregated content
-/// of all of these files.
-pub fn get_aggregated_tomls(ddir: PathBuf) -> String {
-    let toml_files = get_list_of_toml_files_in_dir(ddir);
+/// This method will return the paths of all the config files in Eddie's config directory (see
+/// [get_proper_config_directory]), sorted so that the order in which they're merged
+/// is stable. If `ddir` is a file then it is the only config file, whatever its extension.
+pub fn get_config_files(ddir: PathBuf) -> Vec<PathBuf> {
+    if ddir.is_file() {
+        return vec![ddir];
+    }
+
+    let mut toml_files = get_list_of_config_files_in_dir(ddir);
+    toml_files.sort();
 
     toml_files
-        .iter()
-        .map(|e| read_file_contents(e))
-        .collect::<Vec<String>>()
-        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
-    use std::env;
-    use std::fs::File;
+    use std::fs::{create_dir_all, File};
     use std::io::Write;
 
-    use rand::distributions::Alphanumeric;
-    use rand::Rng;
-
     use super::*;
-
-    fn get_random_string(n: usize) -> String {
-        let rng = rand::thread_rng();
-        rng.sample_iter(&Alphanumeric).take(n).collect()
-    }
+    use crate::config_reader::test_util::write_tmp_files;
 
     fn populate_tmp_files() -> (PathBuf, Vec<PathBuf>) {
-        let random_prefix = get_random_string(5);
-
-        let tmp_eddie_name = String::from("eddie-test-") + &random_prefix;
-
-        let mut test_dir = env::temp_dir();
-        test_dir.push(tmp_eddie_name);
-
-        if !test_dir.exists() {
-            create_dir(&test_dir).unwrap();
-        }
-
-        use std::fs::create_dir;
-
-        let sub_folders = vec!["", "subf", "subf/sub1_2", "subf2"];
-
-        for folder in sub_folders {
-            let mut fd = test_dir.clone();
-            fd.push(folder);
-
-            if !fd.exists() {
-                create_dir(fd).unwrap();
-            }
-        }
-
-        let test_file_names = vec![
+        let test_file_names = [
             "chom.toml",
             "poto.toml",
             "qwoto.toml",
             "subf/dfdsf.toml",
             "subf2/agwe.toml",
             "subf/sub1_2/asd.toml",
+            "subf/sub1_2/qwe.yaml",
+            "subf2/zxc.yml",
+            "tyu.json",
         ];
 
-        let mut created_paths = vec![];
-
-        for name in test_file_names {
-            let mut fd = test_dir.clone();
-            fd.push(name);
-
-            if !fd.exists() {
-                File::create(&fd).unwrap();
-            }
-
-            created_paths.push(fd);
-        }
+        let files: Vec<_> = test_file_names.iter().map(|&e| (e, "")).collect();
+        let test_dir = write_tmp_files("aggregator", &files);
+        let created_paths = test_file_names.iter().map(|e| test_dir.join(e)).collect();
 
         (test_dir, created_paths)
     }
 
-    fn write_random_toml_data_to_file(path: PathBuf) {
-        let mut file = File::open(path).unwrap();
+    fn write_toml_data_to_file(path: &PathBuf, data: &str) {
+        let mut file = File::create(path).unwrap();
 
-        file.write_all(b"asdasd");
+        file.write_all(data.as_bytes()).unwrap();
 
-        file.sync_all();
+        file.sync_all().unwrap();
     }
 
     #[test]
-    fn test_find_tomls() {
+    fn test_find_config_files() {
         let (tests_folder, mut expected) = populate_tmp_files();
         expected.sort();
 
-        let mut got = get_list_of_toml_files_in_dir(tests_folder);
+        // files in other formats, and JSON Schemas, are ignored
+        File::create(tests_folder.join("subf/notes.md")).unwrap();
+        File::create(tests_folder.join("eddie.schema.json")).unwrap();
+
+        let mut got = get_list_of_config_files_in_dir(tests_folder.clone());
         got.sort();
 
         assert_eq!(got, expected);
+
+        fs::remove_dir_all(&tests_folder).unwrap();
     }
 
     #[test]
     fn test_read_appropriate_toml_data() {
-        // TODO write toml contents and test that reading them returns the expected aggregated
-        // content.
+        let (tests_folder, paths) = populate_tmp_files();
+
+        write_toml_data_to_file(&paths[0], "[a]\nname = \"A\"");
+        write_toml_data_to_file(&paths[1], "[b]\nname = \"B\"");
+
+        assert_eq!(read_file_contents(&paths[0]).unwrap(), "[a]\nname = \"A\"");
+        assert_eq!(read_file_contents(&paths[1]).unwrap(), "[b]\nname = \"B\"");
+
+        fs::remove_dir_all(&tests_folder).unwrap();
+    }
+
+    #[test]
+    fn test_config_files_are_sorted() {
+        let (tests_folder, mut expected) = populate_tmp_files();
+        expected.sort();
+
+        assert_eq!(get_config_files(tests_folder.clone()), expected);
+
+        fs::remove_dir_all(&tests_folder).unwrap();
+    }
+
+    #[test]
+    fn test_find_project_configs() {
+        let (tests_folder, _) = populate_tmp_files();
+
+        let outer = tests_folder.join("subf");
+        let inner = outer.join("sub1_2");
+
+        create_dir_all(outer.join(".eddie/nested")).unwrap();
+        let outer_file = outer.join(".eddie/nested/build.toml");
+        write_toml_data_to_file(&outer_file, "");
+
+        let inner_file = inner.join("eddie.toml");
+        write_toml_data_to_file(&inner_file, "");
+
+        let got = find_project_configs(&inner, &[]);
+
+        assert_eq!(
+            got,
+            vec![
+                ProjectConfig {
+                    root: inner,
+                    files: vec![inner_file]
+                },
+                ProjectConfig {
+                    root: outer,
+                    files: vec![outer_file]
+                },
+            ]
+        );
+
+        fs::remove_dir_all(&tests_folder).unwrap();
+    }
+
+    #[test]
+    fn test_find_project_configs_skips_global_dirs() {
+        let (tests_folder, _) = populate_tmp_files();
+
+        let global_dir = tests_folder.join("subf2/.eddie");
+        create_dir_all(&global_dir).unwrap();
+        write_toml_data_to_file(&global_dir.join("global.toml"), "");
+
+        let got = find_project_configs(
+            &tests_folder.join("subf2"),
+            &[global_dir.canonicalize().unwrap()],
+        );
+
+        assert_eq!(got, vec![]);
+
+        fs::remove_dir_all(&tests_folder).unwrap();
+    }
+
+    #[test]
+    fn test_config_search_order() {
+        let home = Some(PathBuf::from("/home/arthur"));
+        let order = |vars: &[(&str, &str)], config_path: Option<&Path>| {
+            let var = |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.into());
+
+            search_order(config_path, var, home.clone())
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            order(&[], None),
+            vec![
+                "/home/arthur/.config/eddie (default $XDG_CONFIG_HOME)",
+                "/home/arthur/.eddie (home directory)",
+                "/etc/xdg/eddie (default $XDG_CONFIG_DIRS)",
+                "/etc/eddie (system)",
+            ]
+        );
+        assert_eq!(
+            order(
+                &[
+                    ("XDG_CONFIG_HOME", "/xdg"),
+                    ("XDG_CONFIG_DIRS", "/a:relative:/b")
+                ],
+                None
+            ),
+            vec![
+                "/xdg/eddie ($XDG_CONFIG_HOME)",
+                "/home/arthur/.eddie (home directory)",
+                "/a/eddie ($XDG_CONFIG_DIRS)",
+                "/b/eddie ($XDG_CONFIG_DIRS)",
+                "/etc/eddie (system)",
+            ]
+        );
+
+        // overrides replace the search entirely, `--config` first
+        let vars = [(CONFIG_DIR_VAR, "/configs/work")];
+        assert_eq!(
+            order(&vars, None),
+            vec!["/configs/work ($EDDIE_CONFIG_DIR)"]
+        );
+        assert_eq!(
+            order(&vars, Some(Path::new("demo.toml"))),
+            vec!["demo.toml (--config)"]
+        );
+    }
+
+    #[test]
+    fn test_single_config_file() {
+        let (tests_folder, _) = populate_tmp_files();
+        let file = tests_folder.join("subf/sub1_2/not_a_config.conf");
+        write_toml_data_to_file(&file, "");
+
+        assert_eq!(get_config_files(file.clone()), vec![file.clone()]);
+        assert_eq!(
+            get_proper_config_directory(Some(&file)),
+            Ok(file.canonicalize().unwrap())
+        );
+
+        let missing = tests_folder.join("missing");
+        let searched = get_proper_config_directory(Some(&missing)).unwrap_err();
+        assert_eq!(searched[0].path, missing);
+
+        fs::remove_dir_all(&tests_folder).unwrap();
     }
 }
@@ -0,0 +1,350 @@
+//! The config_checker module lints an already parsed config tree ([check_config]),
+//! looking for problems which don't prevent the config from being loaded but which are
+//! most likely mistakes. It is the backbone of the `eddie check` command.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config_reader::config_structs::AppConfig;
+use crate::config_reader::config_tree::{ConfigTree, NodeId};
+
+#[derive(Debug, PartialEq)]
+pub enum CheckSeverity {
+    Error,
+    Warning,
+}
+
+/// What exactly is wrong with a node.
+#[derive(Debug, PartialEq)]
+pub enum CheckIssueKind {
+    /// The node has neither children nor a command.
+    NeitherGroupNorCommand,
+    /// The node has both a command and children, so the command is ignored.
+    CommandWithChildren,
+    /// A group which doesn't contain any node.
+    EmptyGroup,
+    /// Several siblings share the same display name.
+    DuplicateName(String),
+    /// The node should be executed in an external terminal, but the configured terminal
+    /// emulator can't be found.
+    TerminalEmulatorNotFound(String),
+    /// The `cwd` of the command is not an existing directory.
+    WorkingDirectoryNotFound(PathBuf),
+}
+
+impl fmt::Display for CheckIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckIssueKind::NeitherGroupNorCommand => {
+                write!(f, "this node has neither a `command` nor children")
+            }
+            CheckIssueKind::CommandWithChildren => write!(
+                f,
+                "this node has both a `command` and children, the command will be ignored"
+            ),
+            CheckIssueKind::EmptyGroup => write!(f, "this group doesn't contain any node"),
+            CheckIssueKind::DuplicateName(name) => {
+                write!(f, "the name \"{}\" is used by more than one sibling", name)
+            }
+            CheckIssueKind::TerminalEmulatorNotFound(terminal) => write!(
+                f,
+                "`external = true` but the terminal emulator `{}` is not on PATH",
+                terminal
+            ),
+            CheckIssueKind::WorkingDirectoryNotFound(cwd) => {
+                write!(
+                    f,
+                    "the `cwd` {} is not an existing directory",
+                    cwd.display()
+                )
+            }
+        }
+    }
+}
+
+/// A problem found by [check_config].
+#[derive(Debug, PartialEq)]
+pub struct CheckIssue {
+    /// The key path of the offending node.
+    pub key_path: String,
+    pub kind: CheckIssueKind,
+}
+
+impl CheckIssue {
+    pub fn severity(&self) -> CheckSeverity {
+        match self.kind {
+            CheckIssueKind::EmptyGroup | CheckIssueKind::DuplicateName(_) => CheckSeverity::Warning,
+            _ => CheckSeverity::Error,
+        }
+    }
+}
+
+impl fmt::Display for CheckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key_path = if self.key_path.is_empty() {
+            "<root>"
+        } else {
+            &self.key_path
+        };
+
+        write!(f, "`{}`: {}", key_path, self.kind)
+    }
+}
+
+/// Returns the full path of `program` if it can be found: either directly (if it is a path)
+/// or in one of the directories of the `PATH` environment variable.
+pub fn find_on_path(program: &str) -> Option<PathBuf> {
+    let program_path = Path::new(program);
+
+    if program_path.components().count() > 1 {
+        return if program_path.is_file() {
+            Some(program_path.to_path_buf())
+        } else {
+            None
+        };
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Checks the whole config tree of `config` and returns every problem found.
+pub fn check_config(config: &AppConfig) -> Vec<CheckIssue> {
+    let terminal = &config.eddie_config.terminal_emulator;
+    let terminal_found = find_on_path(terminal).is_some();
+
+    let tree = &config.config_tree;
+    let mut issues = vec![];
+
+    match tree.children(tree.root()).filter(|e| !e.is_empty()) {
+        Some(children) => check_siblings(tree, children, terminal, terminal_found, &mut issues),
+        None => issues.push(CheckIssue {
+            key_path: tree.node(tree.root()).key_path.clone(),
+            kind: CheckIssueKind::EmptyGroup,
+        }),
+    }
+
+    issues
+}
+
+fn check_siblings(
+    tree: &ConfigTree,
+    siblings: &[NodeId],
+    terminal: &str,
+    terminal_found: bool,
+    issues: &mut Vec<CheckIssue>,
+) {
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for &id in siblings {
+        *names.entry(tree.node(id).name.as_str()).or_default() += 1;
+    }
+
+    for &id in siblings {
+        let node = tree.node(id);
+        if names[node.name.as_str()] > 1 {
+            issues.push(CheckIssue {
+                key_path: node.key_path.clone(),
+                kind: CheckIssueKind::DuplicateName(node.name.clone()),
+            });
+        }
+
+        check_node(tree, id, terminal, terminal_found, issues);
+    }
+}
+
+fn check_node(
+    tree: &ConfigTree,
+    id: NodeId,
+    terminal: &str,
+    terminal_found: bool,
+    issues: &mut Vec<CheckIssue>,
+) {
+    let node = tree.node(id);
+    let is_group = tree.is_group(id);
+    let mut issue = |kind| {
+        issues.push(CheckIssue {
+            key_path: node.key_path.clone(),
+            kind,
+        })
+    };
+
+    if !is_group && !node.is_command() {
+        issue(CheckIssueKind::NeitherGroupNorCommand);
+    }
+
+    if is_group && node.is_command() {
+        issue(CheckIssueKind::CommandWithChildren);
+    }
+
+    // the children of generator groups only exist once the group is entered
+    let has_no_children = tree.children(id).is_some_and(|e| e.is_empty());
+    if has_no_children && node.generator.is_none() {
+        issue(CheckIssueKind::EmptyGroup);
+    }
+
+    if !is_group && node.opens_external && !terminal_found {
+        issue(CheckIssueKind::TerminalEmulatorNotFound(
+            terminal.to_string(),
+        ));
+    }
+
+    if let Some(cwd) = node.cwd.as_ref().filter(|e| !e.is_dir()) {
+        issue(CheckIssueKind::WorkingDirectoryNotFound(cwd.clone()));
+    }
+
+    if let Some(children) = tree.children(id) {
+        check_siblings(tree, children, terminal, terminal_found, issues);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config_reader::config_structs::{ConfigNode, EddieConfig, Generator};
+
+    use super::*;
+
+    fn node(key_path: &str, name: &str, command: &str, children: Vec<ConfigNode>) -> ConfigNode {
+        ConfigNode {
+            key_path: key_path.to_string(),
+            name: name.to_string(),
+            description: "".to_string(),
+            command: command.to_string(),
+            children: if children.is_empty() {
+                None
+            } else {
+                Some(children)
+            },
+            ..Default::default()
+        }
+    }
+
+    fn config(children: Vec<ConfigNode>) -> AppConfig {
+        AppConfig {
+            eddie_config: EddieConfig::default(),
+            config_tree: ConfigTree::new(node("", "root", "", children)),
+            merge_conflicts: vec![],
+            merge_warnings: vec![],
+            config_warnings: vec![],
+            watched_paths: vec![],
+            config_location: PathBuf::new(),
+            config_override: None,
+        }
+    }
+
+    #[test]
+    fn test_valid_config_has_no_issues() {
+        let config = config(vec![node(
+            "work",
+            "Work",
+            "",
+            vec![node("work.hi", "Hi", "echo hi", vec![])],
+        )]);
+
+        assert_eq!(check_config(&config), vec![]);
+    }
+
+    #[test]
+    fn test_finds_all_issues() {
+        let mut external = node("work.ext", "External", "htop", vec![]);
+        external.opens_external = true;
+        external.cwd = Some(PathBuf::from("/surely/not/a/real/dir"));
+
+        let mut config = config(vec![node(
+            "work",
+            "Work",
+            "ls",
+            vec![
+                node("work.a", "Same", "echo a", vec![]),
+                node("work.b", "Same", "", vec![]),
+                external,
+            ],
+        )]);
+        config.eddie_config.terminal_emulator = "surely-not-a-real-terminal".to_string();
+
+        let issues = check_config(&config);
+
+        assert_eq!(
+            issues,
+            vec![
+                CheckIssue {
+                    key_path: "work".to_string(),
+                    kind: CheckIssueKind::CommandWithChildren,
+                },
+                CheckIssue {
+                    key_path: "work.a".to_string(),
+                    kind: CheckIssueKind::DuplicateName("Same".to_string()),
+                },
+                CheckIssue {
+                    key_path: "work.b".to_string(),
+                    kind: CheckIssueKind::DuplicateName("Same".to_string()),
+                },
+                CheckIssue {
+                    key_path: "work.b".to_string(),
+                    kind: CheckIssueKind::NeitherGroupNorCommand,
+                },
+                CheckIssue {
+                    key_path: "work.ext".to_string(),
+                    kind: CheckIssueKind::TerminalEmulatorNotFound(
+                        "surely-not-a-real-terminal".to_string()
+                    ),
+                },
+                CheckIssue {
+                    key_path: "work.ext".to_string(),
+                    kind: CheckIssueKind::WorkingDirectoryNotFound(PathBuf::from(
+                        "/surely/not/a/real/dir"
+                    )),
+                },
+            ]
+        );
+        assert_eq!(issues[0].severity(), CheckSeverity::Error);
+        assert_eq!(issues[1].severity(), CheckSeverity::Warning);
+    }
+
+    #[test]
+    fn test_nested_empty_groups() {
+        let mut empty = node("work.empty", "Empty", "", vec![]);
+        empty.children = Some(vec![]);
+        let mut generated = node("work.branches", "Branches", "", vec![]);
+        generated.children = Some(vec![]);
+        generated.generator = Some(Generator {
+            command: "git branch".to_string(),
+            child_command: "git checkout {{item}}".to_string(),
+            ttl: Duration::from_secs(30),
+        });
+
+        let config = config(vec![node("work", "Work", "", vec![empty, generated])]);
+        let issues = check_config(&config);
+
+        assert_eq!(
+            issues,
+            vec![CheckIssue {
+                key_path: "work.empty".to_string(),
+                kind: CheckIssueKind::EmptyGroup,
+            }]
+        );
+        assert_eq!(issues[0].severity(), CheckSeverity::Warning);
+    }
+
+    #[test]
+    fn test_empty_config() {
+        assert_eq!(
+            check_config(&config(vec![])),
+            vec![CheckIssue {
+                key_path: "".to_string(),
+                kind: CheckIssueKind::EmptyGroup,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_on_path() {
+        assert!(find_on_path("sh").is_some());
+        assert!(find_on_path("surely-not-a-real-program").is_none());
+    }
+}
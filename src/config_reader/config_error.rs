@@ -0,0 +1,273 @@
+//! The config_error module contains the [ConfigError] type, which describes a problem
+//! found while turning the merged config tables into the config tree, together with
+//! the place where the problem was found (file, TOML key path, and line/column).
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// What exactly is wrong with the config.
+#[derive(Debug, PartialEq)]
+pub enum ConfigErrorKind {
+    /// A required key is not present in a table.
+    MissingKey(String),
+    /// A key is present but its value doesn't match the schema (see
+    /// [crate::config_reader::config_schema]).
+    InvalidValue { key: String, message: String },
+    /// A key which is not part of the schema, and is not a child table either.
+    UnknownKey(String),
+    /// A value which should be a group or command table is something else.
+    NotATable { found: &'static str },
+    /// The command uses a `{{placeholder}}` which is not one of its arguments.
+    UndeclaredArgument(String),
+    /// Two keys which can't be used together, e.g. `command` and `script`.
+    ConflictingKeys { key: String, other: String },
+    /// A `ref` to a node which doesn't exist.
+    DanglingReference(String),
+    /// A `ref` to a group, while only commands can be aliased.
+    ReferenceToGroup(String),
+    /// A `${variable}` which is neither in `vars` nor an environment variable, and has no
+    /// default, in a value which the shell doesn't expand (e.g. `cwd`).
+    UndefinedVariable(String),
+    /// Aliases which refer to each other, e.g. `a -> b -> a`.
+    ReferenceCycle(Vec<String>),
+    /// A setting or child table of an alias, which would be ignored since the alias is a
+    /// copy of the node it refers to.
+    AliasOverride(String),
+    /// A key of the `ship` table of a project which is not trusted, and which is ignored
+    /// since it would change how every command runs (e.g. `shell`).
+    UntrustedProjectKey(String),
+    /// One of the arguments of a command is not valid.
+    InvalidArgument { name: String, message: String },
+    /// A config file which isn't valid TOML, YAML or JSON.
+    InvalidSyntax(String),
+    /// None of the places where the global config is searched for exists. They're listed in
+    /// the order in which they were searched.
+    ConfigNotFound(Vec<String>),
+}
+
+impl fmt::Display for ConfigErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigErrorKind::MissingKey(_) => write!(f, "this required key is missing"),
+            ConfigErrorKind::InvalidValue { message, .. } => write!(f, "{}", message),
+            ConfigErrorKind::UnknownKey(_) => write!(f, "unknown key, it will be ignored"),
+            ConfigErrorKind::NotATable { found } => {
+                write!(f, "expected a group or command table but found a {}", found)
+            }
+            ConfigErrorKind::UndeclaredArgument(name) => write!(
+                f,
+                "the placeholder `{{{{{}}}}}` doesn't match any of the command's `args`",
+                name
+            ),
+            ConfigErrorKind::ConflictingKeys { key, other } => {
+                write!(f, "`{}` can't be used together with `{}`", key, other)
+            }
+            ConfigErrorKind::DanglingReference(target) => {
+                write!(f, "there is no node at `{}`", target)
+            }
+            ConfigErrorKind::ReferenceToGroup(target) => {
+                write!(
+                    f,
+                    "`{}` is a group, only commands can be referenced",
+                    target
+                )
+            }
+            ConfigErrorKind::UndefinedVariable(name) => write!(
+                f,
+                "the variable `{}` is not defined (use `${{{}:-default}}` to give it a default)",
+                name, name
+            ),
+            ConfigErrorKind::ReferenceCycle(cycle) => {
+                write!(f, "the references form a cycle: {}", cycle.join(" -> "))
+            }
+            ConfigErrorKind::AliasOverride(key) => write!(
+                f,
+                "`{}` can't be set on an alias, which only takes `name`, `description`, \
+                 `order`, `pinned` and `when`",
+                key
+            ),
+            ConfigErrorKind::UntrustedProjectKey(key) => write!(
+                f,
+                "`{}` is ignored since this project is not trusted (add its directory to the \
+                 `trusted_projects` of the global `ship` table to trust it)",
+                key
+            ),
+            ConfigErrorKind::InvalidArgument { name, message } => {
+                write!(f, "argument `{}`: {}", name, message)
+            }
+            ConfigErrorKind::InvalidSyntax(message) => {
+                write!(f, "the file can't be parsed: {}", message)
+            }
+            ConfigErrorKind::ConfigNotFound(searched) => {
+                write!(
+                    f,
+                    "no config was found, these places were searched in order:"
+                )?;
+                for place in searched {
+                    write!(f, "\n    {}", place)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A problem found in the config, with as much information as possible about where it is.
+/// The same type is used for warnings, which don't stop Eddie from starting.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    /// The file that defines the offending table, if it could be determined.
+    pub file: Option<PathBuf>,
+    /// The dotted TOML path of the offending key, e.g. `work.general.echo_hi.external`.
+    pub key_path: String,
+    /// The 1-based line and column of the offending key in `file`. If the key itself is
+    /// missing then this is the location of the header of its table.
+    pub location: Option<(usize, usize)>,
+    pub kind: ConfigErrorKind,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.location) {
+            (Some(file), Some((line, col))) => write!(f, "{}:{}:{}: ", file.display(), line, col)?,
+            (Some(file), None) => write!(f, "{}: ", file.display())?,
+            (None, _) => {}
+        }
+
+        // errors about the config as a whole aren't about any key
+        if !self.key_path.is_empty() {
+            write!(f, "`{}`: ", self.key_path)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// All the [ConfigError]s found while reading the config.
+#[derive(Debug, PartialEq)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Found {} problem(s) in the config:", self.0.len())?;
+
+        for error in &self.0 {
+            writeln!(f, "  {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tries to find where `key` (or, if it is `None`, the header of the table itself) of the
+/// table at `table_path` is defined in the TOML `content`. The returned location is 1-based.
+///
+/// This is a best effort: it understands `[table.headers]` followed by `key = value` lines,
+/// which is how Eddie configs are usually written, and gives up on anything fancier. A key
+/// holding a table is found at the header of that table.
+pub fn locate_key(content: &str, table_path: &str, key: Option<&str>) -> Option<(usize, usize)> {
+    let mut current_table = String::new();
+    let mut header_location = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.starts_with('[') && !trimmed.starts_with("[[") {
+            if let Some(end) = trimmed.find(']') {
+                current_table = normalize_key_path(&trimmed[1..end]);
+
+                if current_table == table_path {
+                    header_location = Some((idx + 1, indent + 1));
+
+                    if key.is_none() {
+                        return header_location;
+                    }
+                } else if let Some(key) = key {
+                    if current_table == format!("{}.{}", table_path, key)
+                        || (table_path.is_empty() && current_table == key)
+                    {
+                        return Some((idx + 1, indent + 1));
+                    }
+                }
+            }
+        } else if let Some(key) = key {
+            if current_table != table_path {
+                continue;
+            }
+
+            if let Some(eq) = trimmed.find('=') {
+                if normalize_key_path(&trimmed[..eq]) == key {
+                    return Some((idx + 1, indent + 1));
+                }
+            }
+        }
+    }
+
+    header_location
+}
+
+fn normalize_key_path(path: &str) -> String {
+    path.split('.')
+        .map(|e| e.trim().trim_matches('"').trim_matches('\''))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"
+[work]
+name = "Work"
+
+[ work."general" ]
+  name = "General"
+description = "stuff"
+"#;
+
+    #[test]
+    fn test_locate_table_header() {
+        assert_eq!(locate_key(CONTENT, "work", None), Some((2, 1)));
+        assert_eq!(locate_key(CONTENT, "work.general", None), Some((5, 1)));
+    }
+
+    #[test]
+    fn test_locate_key_inside_table() {
+        assert_eq!(locate_key(CONTENT, "work", Some("name")), Some((3, 1)));
+        assert_eq!(
+            locate_key(CONTENT, "work.general", Some("name")),
+            Some((6, 3))
+        );
+    }
+
+    #[test]
+    fn test_locate_child_table() {
+        assert_eq!(locate_key(CONTENT, "work", Some("general")), Some((5, 1)));
+        assert_eq!(locate_key(CONTENT, "", Some("work")), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_missing_key_falls_back_to_header() {
+        assert_eq!(
+            locate_key(CONTENT, "work", Some("description")),
+            Some((2, 1))
+        );
+        assert_eq!(locate_key(CONTENT, "nope", Some("name")), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let error = ConfigError {
+            file: Some(PathBuf::from("work.toml")),
+            key_path: "work.general.name".to_string(),
+            location: Some((5, 1)),
+            kind: ConfigErrorKind::MissingKey("name".to_string()),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "work.toml:5:1: `work.general.name`: this required key is missing"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! The config_format module abstracts over the formats in which config files can be written:
+//! TOML, YAML and JSON ([ConfigFormat]). Whatever their format, files are parsed into the same
+//! [Value] (a TOML value), so the rest of the config reader doesn't need to care about it.
+
+use std::path::Path;
+
+use toml::{value::Map, Value};
+
+use crate::config_reader::config_error;
+
+/// Why a config file couldn't be parsed.
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// The 1-based line and column where parsing failed, if the parser tells it.
+    pub location: Option<(usize, usize)>,
+}
+
+impl SyntaxError {
+    /// Builds the error from the `message` of a parser and its 1-based `location`. Parsers
+    /// repeat the location at the end of their messages, which is dropped since it is
+    /// reported on its own.
+    fn new(message: String, location: Option<(usize, usize)>) -> SyntaxError {
+        let message = match (message.rfind(" at line "), location) {
+            (Some(idx), Some(_)) => message[..idx].to_string(),
+            _ => message,
+        };
+
+        SyntaxError { message, location }
+    }
+}
+
+/// The format of a config file, which is told by its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Returns the format of the file at `path`, or `None` if it is not a config file.
+    pub fn from_path(path: &Path) -> Option<ConfigFormat> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Parses `content`, which is written in this format, into a [Value].
+    pub fn parse(self, content: &str) -> Result<Value, SyntaxError> {
+        // unlike TOML, an empty YAML or JSON document is not an empty table
+        if content.trim().is_empty() {
+            return Ok(Value::Table(Map::new()));
+        }
+
+        match self {
+            ConfigFormat::Toml => content.parse::<Value>().map_err(|e| {
+                let location = e.line_col().map(|(line, col)| (line + 1, col + 1));
+                SyntaxError::new(e.to_string(), location)
+            }),
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(|e| {
+                let location = e.location().map(|e| (e.line(), e.column()));
+                SyntaxError::new(e.to_string(), location)
+            }),
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| {
+                // errors which aren't about the syntax, like a `null`, have no location
+                let location = Some((e.line(), e.column())).filter(|&(line, _)| line > 0);
+                SyntaxError::new(e.to_string(), location)
+            }),
+        }
+    }
+
+    /// Tries to find where `key` (or, if it is `None`, the table itself) of the table at
+    /// `table_path` is defined in `content`. The returned location is 1-based. See
+    /// [config_error::locate_key] for TOML files.
+    pub fn locate_key(
+        self,
+        content: &str,
+        table_path: &str,
+        key: Option<&str>,
+    ) -> Option<(usize, usize)> {
+        let keys = match self {
+            ConfigFormat::Toml => return config_error::locate_key(content, table_path, key),
+            ConfigFormat::Yaml => yaml_keys(content),
+            ConfigFormat::Json => json_keys(content),
+        };
+
+        let location_of = |key_path: &str| {
+            keys.iter()
+                .find(|(e, _)| e == key_path)
+                .map(|&(_, location)| location)
+        };
+
+        key.and_then(|key| location_of(&join(table_path, key)))
+            .or_else(|| location_of(table_path))
+    }
+}
+
+fn join(table_path: &str, key: &str) -> String {
+    if table_path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", table_path, key)
+    }
+}
+
+/// Returns the key path and the location of every key of the YAML `content`.
+///
+/// This is a best effort: it understands block mappings (`key: value` lines, nested by
+/// indentation), which is how Eddie configs are usually written, and skips the rest. The
+/// bodies of block scalars (`|` and `>`), like scripts, are skipped too.
+fn yaml_keys(content: &str) -> Vec<(String, (usize, usize))> {
+    let mut keys = vec![];
+    // the indentation and the name of the keys which contain the current line
+    let mut parents: Vec<(usize, String)> = vec![];
+    // the indentation of the line which starts the block scalar being skipped, whose body
+    // is made of the lines indented further (and of blank lines)
+    let mut block_scalar: Option<usize> = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some(block_indent) = block_scalar {
+            if trimmed.is_empty() || indent > block_indent {
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // the items of sequences aren't tables, but they can start block scalars too
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            let value = item.find(':').map_or(item, |colon| &item[colon + 1..]);
+            if is_block_scalar(value) {
+                block_scalar = Some(indent);
+            }
+            continue;
+        }
+
+        let key = match trimmed.find(':') {
+            Some(colon) => {
+                if is_block_scalar(&trimmed[colon + 1..]) {
+                    block_scalar = Some(indent);
+                }
+                trimmed[..colon].trim().trim_matches('"').trim_matches('\'')
+            }
+            None => continue,
+        };
+
+        while parents.last().is_some_and(|&(e, _)| e >= indent) {
+            parents.pop();
+        }
+        parents.push((indent, key.to_string()));
+
+        let key_path = parents
+            .iter()
+            .map(|(_, e)| e.as_str())
+            .collect::<Vec<_>>()
+            .join(".");
+        keys.push((key_path, (idx + 1, indent + 1)));
+    }
+
+    keys
+}
+
+/// Returns whether the YAML `value` (what follows a key or a `- `) starts a block scalar.
+fn is_block_scalar(value: &str) -> bool {
+    let value = value.trim_start();
+    value.starts_with('|') || value.starts_with('>')
+}
+
+/// Returns the key path and the location of every key of the JSON `content`, except the
+/// ones inside arrays.
+fn json_keys(content: &str) -> Vec<(String, (usize, usize))> {
+    let mut keys = vec![];
+    // the key of every open object, `None` for arrays and for the root object
+    let mut parents: Vec<Option<String>> = vec![];
+    let mut last_string: Option<(String, (usize, usize))> = None;
+    let mut pending_key: Option<String> = None;
+
+    let (mut line, mut col) = (1, 0);
+    let mut chars = content.chars();
+
+    while let Some(c) = chars.next() {
+        col += 1;
+
+        match c {
+            '\n' => {
+                line += 1;
+                col = 0;
+            }
+            '"' => {
+                let location = (line, col);
+                let mut string = String::new();
+
+                while let Some(c) = chars.next() {
+                    col += 1;
+                    match c {
+                        '\\' => {
+                            col += 1;
+                            string.extend(chars.next());
+                        }
+                        '"' => break,
+                        c => string.push(c),
+                    }
+                }
+
+                last_string = Some((string, location));
+            }
+            ':' => {
+                if let Some((key, location)) = last_string.take() {
+                    if parents.iter().skip(1).all(|e| e.is_some()) && !parents.is_empty() {
+                        let mut key_path: Vec<&str> = parents
+                            .iter()
+                            .skip(1)
+                            .flatten()
+                            .map(|e| e.as_str())
+                            .collect();
+                        key_path.push(&key);
+                        keys.push((key_path.join("."), location));
+                    }
+                    pending_key = Some(key);
+                }
+            }
+            '{' => parents.push(pending_key.take()),
+            '[' => {
+                pending_key = None;
+                parents.push(None);
+            }
+            '}' | ']' => {
+                parents.pop();
+            }
+            ',' => {
+                pending_key = None;
+                last_string = None;
+            }
+            _ => {}
+        }
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const YAML: &str = r#"
+ship:
+  name: Goldar
+
+work:
+  name: Work
+  general:
+    name: "General"
+    args:
+      - name: branch
+  "hi":
+    command: echo hi
+"#;
+
+    const JSON: &str = r#"{
+  "ship": { "name": "Goldar" },
+  "work": {
+    "name": "Work",
+    "general": {
+      "name": "General",
+      "args": [{ "name": "branch" }]
+    },
+    "hi": { "command": "echo \"hi\"" }
+  }
+}"#;
+
+    #[test]
+    fn test_formats_are_told_by_the_extension() {
+        let format = |path: &str| ConfigFormat::from_path(&PathBuf::from(path));
+
+        assert_eq!(format("a/main.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(format("a/main.yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(format("a/main.yaml"), Some(ConfigFormat::Yaml));
+        assert_eq!(format("a/main.json"), Some(ConfigFormat::Json));
+        assert_eq!(format("a/main.md"), None);
+        assert_eq!(format("a/Makefile"), None);
+    }
+
+    #[test]
+    fn test_all_formats_are_parsed_into_the_same_value() {
+        let toml = r#"
+[ship]
+name = "Goldar"
+
+[work]
+name = "Work"
+
+[work.general]
+name = "General"
+args = [{ name = "branch" }]
+
+[work.hi]
+command = 'echo "hi"'
+"#;
+        let yaml = YAML.replace("echo hi", "echo \"hi\"");
+        let expected = ConfigFormat::Toml.parse(toml).unwrap();
+
+        assert_eq!(ConfigFormat::Yaml.parse(&yaml).unwrap(), expected);
+        assert_eq!(ConfigFormat::Json.parse(JSON).unwrap(), expected);
+        assert_eq!(
+            ConfigFormat::Yaml.parse("\n").unwrap(),
+            Value::Table(Map::new())
+        );
+        assert!(ConfigFormat::Yaml.parse("a: [").is_err());
+        assert!(ConfigFormat::Json.parse("{\"a\": null}").is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors_are_located() {
+        let location = |format: ConfigFormat, content| format.parse(content).unwrap_err().location;
+
+        assert_eq!(location(ConfigFormat::Toml, "[a]\nname = \n"), Some((2, 8)));
+        assert_eq!(location(ConfigFormat::Yaml, "a:\n  b: [\n"), Some((3, 1)));
+        assert_eq!(location(ConfigFormat::Json, "{\n  \"a\": }"), Some((2, 8)));
+
+        let error = ConfigFormat::Toml.parse("[a\n").unwrap_err();
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+    }
+
+    #[test]
+    fn test_locate_yaml_keys() {
+        let locate = |table_path, key| ConfigFormat::Yaml.locate_key(YAML, table_path, key);
+
+        assert_eq!(locate("work.general", None), Some((7, 3)));
+        assert_eq!(locate("work.general", Some("name")), Some((8, 5)));
+        assert_eq!(locate("work.hi", Some("command")), Some((12, 5)));
+        // falls back to the table if the key is missing
+        assert_eq!(locate("work", Some("description")), Some((5, 1)));
+        assert_eq!(locate("nope", None), None);
+    }
+
+    #[test]
+    fn test_yaml_block_scalars_are_skipped() {
+        let yaml = r#"
+work:
+  hi:
+    script: |
+      name: not a key
+
+      print("a: b")
+    command: >-
+      echo
+        description: neither
+    steps:
+      - |
+        name: still not a key
+  bye:
+    name: Bye
+"#;
+        let locate = |table_path, key| ConfigFormat::Yaml.locate_key(yaml, table_path, key);
+
+        assert_eq!(locate("work.hi", Some("name")), Some((3, 3)));
+        assert_eq!(locate("work.hi", Some("description")), Some((3, 3)));
+        assert_eq!(locate("work.hi", Some("command")), Some((8, 5)));
+        assert_eq!(locate("work.hi", Some("steps")), Some((11, 5)));
+        assert_eq!(locate("work.bye", Some("name")), Some((15, 5)));
+    }
+
+    #[test]
+    fn test_locate_json_keys() {
+        let locate = |table_path, key| ConfigFormat::Json.locate_key(JSON, table_path, key);
+
+        assert_eq!(locate("ship", Some("name")), Some((2, 13)));
+        assert_eq!(locate("work.general", Some("name")), Some((6, 7)));
+        assert_eq!(locate("work.hi", Some("command")), Some((9, 13)));
+        assert_eq!(locate("work", Some("description")), Some((3, 3)));
+        assert_eq!(locate("nope", None), None);
+    }
+}
@@ -0,0 +1,564 @@
+//! The config_merger module parses each config file on its own and deep-merges the
+//! resulting TOML tables into a single [Value] ([merge_files]).
+//!
+//! Tables which are defined in several files (e.g. two files both adding children to
+//! `[work]`) are merged key by key. If the same non-table key is defined more than once
+//! then the first definition is kept and a [MergeConflict] naming both files is reported.
+//! Files that can't be read are skipped, and a [MergeWarning] is reported for them instead
+//! of failing the whole configuration. Files that can't be parsed are reported as
+//! [ConfigError]s, with the location given by the parser.
+//!
+//! Every file can include other files through a top level `include` array (see
+//! [IncludeSchema]). Included files are merged right after the file including them, so the
+//! including file takes precedence over them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::{value::Map, Value};
+
+use crate::config_reader::config_aggregator;
+use crate::config_reader::config_error::{ConfigError, ConfigErrorKind};
+use crate::config_reader::config_format::ConfigFormat;
+use crate::config_reader::config_schema::IncludeSchema;
+use crate::config_reader::expansion;
+
+/// A key which has been defined (with a non-table value) by more than one file.
+#[derive(Debug, PartialEq)]
+pub struct MergeConflict {
+    /// The dotted path of the key, e.g. `work.general.name`.
+    pub key_path: String,
+    /// The file whose definition was kept.
+    pub kept_file: PathBuf,
+    /// The file whose definition was ignored.
+    pub ignored_file: PathBuf,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is defined in both {} and {}, keeping the value from {}",
+            self.key_path,
+            self.kept_file.display(),
+            self.ignored_file.display(),
+            self.kept_file.display()
+        )
+    }
+}
+
+/// A file which was skipped while merging.
+#[derive(Debug, PartialEq)]
+pub struct MergeWarning {
+    pub file: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for MergeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "skipping {}: {}", self.file.display(), self.message)
+    }
+}
+
+/// The result of merging a set of config files.
+#[derive(Debug)]
+pub struct MergedConfig {
+    /// The merged root table.
+    pub value: Value,
+    pub conflicts: Vec<MergeConflict>,
+    pub warnings: Vec<MergeWarning>,
+    /// The files that couldn't be parsed.
+    pub errors: Vec<ConfigError>,
+    /// The file that first defined each key path.
+    origins: HashMap<String, PathBuf>,
+    /// The content of every file that was merged.
+    sources: HashMap<PathBuf, String>,
+    /// The key path under which each included file was mounted (see [IncludeSchema]).
+    mounts: HashMap<PathBuf, String>,
+}
+
+impl MergedConfig {
+    /// Returns the file that defined `key_path`, or the closest of its parents.
+    pub fn origin_of(&self, key_path: &str) -> Option<&PathBuf> {
+        find_origin(&self.origins, key_path)
+    }
+
+    /// Returns the content of `file`, if it was one of the merged files.
+    pub fn source_of(&self, file: &Path) -> Option<&str> {
+        self.sources.get(file).map(|e| e.as_str())
+    }
+
+    /// Returns every file that was merged, including the included ones.
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.sources.keys()
+    }
+
+    /// Returns the path of the table at `table_path` as written in `file`, which differs
+    /// from `table_path` if the file was mounted under a prefix.
+    pub fn local_table_path<'p>(&self, file: &Path, table_path: &'p str) -> &'p str {
+        match self.mounts.get(file) {
+            Some(prefix) if table_path == prefix => "",
+            Some(prefix) => table_path
+                .strip_prefix(prefix.as_str())
+                .and_then(|e| e.strip_prefix('.'))
+                .unwrap_or(table_path),
+            None => table_path,
+        }
+    }
+}
+
+fn find_origin<'a>(origins: &'a HashMap<String, PathBuf>, key_path: &str) -> Option<&'a PathBuf> {
+    let mut path = key_path;
+
+    loop {
+        if let Some(origin) = origins.get(path) {
+            return Some(origin);
+        }
+
+        path = &path[..path.rfind('.')?];
+    }
+}
+
+/// Keeps track of which file defined each key, so that conflicts can name both files, and
+/// of the files being included, so that include cycles can be detected.
+struct MergeContext {
+    origins: HashMap<String, PathBuf>,
+    conflicts: Vec<MergeConflict>,
+    warnings: Vec<MergeWarning>,
+    errors: Vec<ConfigError>,
+    sources: HashMap<PathBuf, String>,
+    mounts: HashMap<PathBuf, String>,
+    /// The files whose includes are being merged, the outermost first.
+    include_stack: Vec<PathBuf>,
+    /// The files merged so far, together with the prefix they were mounted under.
+    merged_files: HashSet<(PathBuf, String)>,
+}
+
+/// Reads and parses every file in `files`, and merges them in order.
+pub fn merge_files(files: &[PathBuf]) -> MergedConfig {
+    let mut sources = vec![];
+    let mut read_warnings = vec![];
+
+    for file in files {
+        match config_aggregator::read_file_contents(file) {
+            Ok(content) => sources.push((file.clone(), content)),
+            Err(e) => read_warnings.push(MergeWarning {
+                file: file.clone(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    let mut merged = merge_sources(&sources);
+    read_warnings.append(&mut merged.warnings);
+    merged.warnings = read_warnings;
+
+    merged
+}
+
+/// Parses every `(file, content)` pair in `sources` and merges them in order.
+pub fn merge_sources(sources: &[(PathBuf, String)]) -> MergedConfig {
+    let mut merged = Map::new();
+    let mut context = MergeContext {
+        origins: HashMap::new(),
+        conflicts: vec![],
+        warnings: vec![],
+        errors: vec![],
+        sources: HashMap::new(),
+        mounts: HashMap::new(),
+        include_stack: vec![],
+        merged_files: HashSet::new(),
+    };
+
+    for (file, content) in sources {
+        merge_source(&mut merged, file, content, "", &mut context);
+    }
+
+    MergedConfig {
+        value: Value::Table(merged),
+        conflicts: context.conflicts,
+        warnings: context.warnings,
+        errors: context.errors,
+        origins: context.origins,
+        sources: context.sources,
+        mounts: context.mounts,
+    }
+}
+
+/// Parses `content` (the content of `file`) and merges it into `target` under the key path
+/// `mount`, followed by the files it includes.
+fn merge_source(
+    target: &mut Map<String, Value>,
+    file: &Path,
+    content: &str,
+    mount: &str,
+    context: &mut MergeContext,
+) {
+    let canonical_file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    if !context
+        .merged_files
+        .insert((canonical_file.clone(), mount.to_string()))
+    {
+        // the file has already been merged through another include
+        return;
+    }
+
+    let mut error = |message: String, location: Option<(usize, usize)>| {
+        context.errors.push(ConfigError {
+            file: Some(file.to_path_buf()),
+            key_path: String::new(),
+            location,
+            kind: ConfigErrorKind::InvalidSyntax(message),
+        })
+    };
+
+    let format = ConfigFormat::from_path(file).unwrap_or(ConfigFormat::Toml);
+    let mut table = match format.parse(content) {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => return error("the file does not contain a table".to_string(), None),
+        Err(e) => return error(e.message, e.location),
+    };
+
+    let mut warning = |message: String| {
+        context.warnings.push(MergeWarning {
+            file: file.to_path_buf(),
+            message,
+        })
+    };
+
+    let includes = match table
+        .remove("include")
+        .map(|e| e.try_into::<Vec<IncludeSchema>>())
+    {
+        Some(Ok(includes)) => includes,
+        Some(Err(e)) => {
+            warning(format!("ignoring the `include` key: {}", e));
+            vec![]
+        }
+        None => vec![],
+    };
+
+    context
+        .sources
+        .insert(file.to_path_buf(), content.to_string());
+    if !mount.is_empty() {
+        context.mounts.insert(file.to_path_buf(), mount.to_string());
+    }
+
+    // mounting a file means nesting its root table under the tables of the prefix
+    let mut mounted = table;
+    for key in mount.rsplit('.').filter(|e| !e.is_empty()) {
+        let mut parent = Map::new();
+        parent.insert(key.to_string(), Value::Table(mounted));
+        mounted = parent;
+    }
+    merge_tables(target, mounted, "", file, context);
+
+    context.include_stack.push(canonical_file);
+    for include in includes {
+        let (pattern, prefix) = match include {
+            IncludeSchema::Path(path) => (path, String::new()),
+            IncludeSchema::Mounted { path, prefix } => (path, prefix),
+        };
+        let include_mount = match (mount.is_empty(), prefix.is_empty()) {
+            (_, true) => mount.to_string(),
+            (true, false) => prefix,
+            (false, false) => format!("{}.{}", mount, prefix),
+        };
+
+        for included_file in resolve_include(&pattern, file, context) {
+            merge_included_file(target, &included_file, &include_mount, context);
+        }
+    }
+    context.include_stack.pop();
+}
+
+/// Returns the files matched by the include `pattern` found in `file`, sorted by name.
+fn resolve_include(pattern: &str, file: &Path, context: &mut MergeContext) -> Vec<PathBuf> {
+    let path = expansion::expand_path(pattern, file.parent());
+    let path_str = path.to_string_lossy();
+
+    let mut warning = |message: String| {
+        context.warnings.push(MergeWarning {
+            file: file.to_path_buf(),
+            message,
+        })
+    };
+
+    if !path_str.contains(['*', '?', '[']) {
+        if !path.is_file() {
+            warning(format!(
+                "the included file {} doesn't exist",
+                path.display()
+            ));
+            return vec![];
+        }
+
+        return vec![path];
+    }
+
+    match glob::glob(&path_str) {
+        Ok(paths) => {
+            let mut files: Vec<PathBuf> = paths
+                .filter_map(|e| e.ok())
+                .filter(|e| e.is_file())
+                .collect();
+            files.sort();
+            files
+        }
+        Err(e) => {
+            warning(format!("invalid include pattern `{}`: {}", pattern, e));
+            vec![]
+        }
+    }
+}
+
+/// Reads `file` and merges it into `target`, unless it is already being included (which
+/// would mean that the includes form a cycle).
+fn merge_included_file(
+    target: &mut Map<String, Value>,
+    file: &Path,
+    mount: &str,
+    context: &mut MergeContext,
+) {
+    let canonical_file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+
+    if let Some(start) = context
+        .include_stack
+        .iter()
+        .position(|e| *e == canonical_file)
+    {
+        let cycle: Vec<_> = context.include_stack[start..]
+            .iter()
+            .chain(std::iter::once(&canonical_file))
+            .map(|e| e.display().to_string())
+            .collect();
+
+        context.warnings.push(MergeWarning {
+            file: file.to_path_buf(),
+            message: format!("the includes form a cycle: {}", cycle.join(" -> ")),
+        });
+        return;
+    }
+
+    match config_aggregator::read_file_contents(file) {
+        Ok(content) => merge_source(target, file, &content, mount, context),
+        Err(e) => context.warnings.push(MergeWarning {
+            file: file.to_path_buf(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+fn merge_tables(
+    target: &mut Map<String, Value>,
+    source: Map<String, Value>,
+    prefix: &str,
+    file: &Path,
+    context: &mut MergeContext,
+) {
+    for (key, value) in source {
+        let key_path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match (target.get_mut(&key), value) {
+            (None, value) => {
+                context.origins.insert(key_path, file.to_path_buf());
+                target.insert(key, value);
+            }
+            (Some(Value::Table(existing)), Value::Table(new)) => {
+                merge_tables(existing, new, &key_path, file, context);
+            }
+            (Some(_), _) => {
+                let kept_file = find_origin(&context.origins, &key_path)
+                    .cloned()
+                    .unwrap_or_else(|| file.to_path_buf());
+
+                context.conflicts.push(MergeConflict {
+                    key_path,
+                    kept_file,
+                    ignored_file: file.to_path_buf(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_reader::test_util::write_tmp_files;
+
+    fn sources(files: &[(&str, &str)]) -> Vec<(PathBuf, String)> {
+        files
+            .iter()
+            .map(|(f, c)| (PathBuf::from(f), c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merges_tables_defined_in_several_files() {
+        let merged = merge_sources(&sources(&[
+            ("a.toml", "[work]\nname = \"Work\"\n[work.a]\nname = \"A\""),
+            ("b.toml", "[work.b]\nname = \"B\""),
+        ]));
+
+        let expected = "[work]\nname = \"Work\"\n[work.a]\nname = \"A\"\n[work.b]\nname = \"B\""
+            .parse::<Value>()
+            .unwrap();
+
+        assert_eq!(merged.value, expected);
+        assert_eq!(merged.conflicts, vec![]);
+        assert_eq!(merged.warnings, vec![]);
+    }
+
+    #[test]
+    fn test_reports_conflicts_with_both_files() {
+        let merged = merge_sources(&sources(&[
+            ("a.toml", "[work]\nname = \"Work\""),
+            ("b.toml", "[work]\nname = \"Other work\""),
+        ]));
+
+        assert_eq!(
+            merged.value["work"]["name"],
+            Value::String("Work".to_string())
+        );
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                key_path: "work.name".to_string(),
+                kept_file: PathBuf::from("a.toml"),
+                ignored_file: PathBuf::from("b.toml"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_conflicts_inside_tables_name_the_defining_file() {
+        let merged = merge_sources(&sources(&[
+            ("a.toml", "[work.a]\ncommand = \"ls\""),
+            ("b.toml", "[work]\na = \"not a table\""),
+        ]));
+
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                key_path: "work.a".to_string(),
+                kept_file: PathBuf::from("a.toml"),
+                ignored_file: PathBuf::from("b.toml"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = write_tmp_files(
+            "includes",
+            &[
+                (
+                    "main/eddie.toml",
+                    r#"include = ["../parts/*.toml", { path = "../team.toml", prefix = "work.team" }]
+[work]
+name = "Work"
+"#,
+                ),
+                ("parts/a.toml", "[a]\nname = \"A\""),
+                (
+                    "parts/b.toml",
+                    "[work]\nname = \"Other work\"\n[b]\nname = \"B\"",
+                ),
+                ("team.toml", "[deploy]\nname = \"Deploy\""),
+            ],
+        );
+
+        let merged = merge_files(&[dir.join("main/eddie.toml")]);
+
+        let expected = r#"
+[work]
+name = "Work"
+[work.team.deploy]
+name = "Deploy"
+[a]
+name = "A"
+[b]
+name = "B"
+"#
+        .parse::<Value>()
+        .unwrap();
+
+        assert_eq!(merged.value, expected);
+        assert_eq!(merged.warnings, vec![]);
+        // the including file takes precedence over the included ones
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].kept_file, dir.join("main/eddie.toml"));
+
+        let team_file = dir.join("main/../team.toml");
+        assert_eq!(merged.origin_of("work.team.deploy.name"), Some(&team_file));
+        assert_eq!(
+            merged.local_table_path(&team_file, "work.team.deploy"),
+            "deploy"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycles_are_skipped() {
+        let dir = write_tmp_files(
+            "includes",
+            &[
+                (
+                    "a.toml",
+                    "include = [\"b.toml\", \"missing.toml\"]\n[a]\nname = \"A\"",
+                ),
+                ("b.toml", "include = [\"a.toml\"]\n[b]\nname = \"B\""),
+            ],
+        );
+
+        let merged = merge_files(&[dir.join("a.toml")]);
+
+        assert_eq!(merged.value.as_table().unwrap().len(), 2);
+        assert_eq!(merged.conflicts, vec![]);
+
+        let messages: Vec<_> = merged.warnings.iter().map(|e| &e.message).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("the includes form a cycle: "));
+        assert!(messages[0].ends_with("a.toml"));
+        assert!(messages[1].ends_with("missing.toml doesn't exist"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_files_are_reported() {
+        let merged = merge_sources(&sources(&[
+            ("a.toml", "[work]\nname = \"Work\""),
+            ("b.toml", "[broken\nname = "),
+            ("c.json", "[1, 2]"),
+        ]));
+
+        assert_eq!(
+            merged.value,
+            "[work]\nname = \"Work\"".parse::<Value>().unwrap()
+        );
+        assert_eq!(merged.warnings, vec![]);
+
+        let errors: Vec<_> = merged
+            .errors
+            .iter()
+            .map(|e| (e.file.clone().unwrap(), e.location))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (PathBuf::from("b.toml"), Some((1, 8))),
+                (PathBuf::from("c.json"), None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! The config_schema module declares the keys that Eddie understands in its config files.
+//! Group/command tables are described by [NodeSchema] and the `ship` table by
+//! [ShipSchema]. Both are deserialized with `serde`, and the keys they declare are also
+//! used to tell apart child tables and unknown (probably misspelled) keys.
+//!
+//! The whole schema can be exported as a JSON Schema ([config_json_schema]) so that
+//! editors can autocomplete and validate Eddie's config files.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use schemars::gen::SchemaSettings;
+use schemars::schema::{InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use toml::{value::Map, Value};
+
+use crate::config_reader::config_structs::{
+    CommandArg, EddieConfig, NodeOrder, Providers, UnavailableNodes,
+};
+
+/// A group or command table. Any other key holding a table is a child of the node.
+///
+/// The settings of a group (`shell`, `interpreter`, `cwd`, `env`, `external` and
+/// `command_prefix`) are inherited by all its descendants, unless they set their own.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct NodeSchema {
+    /// The name of the group or command, as shown in Eddie's UI. Aliases (see `ref`) can
+    /// leave it out to use the name of the node they refer to.
+    pub name: String,
+    /// A description of what the group contains or what the command does.
+    pub description: Option<String>,
+    /// Makes this node an alias of the command at the given key path (e.g.
+    /// `system.network.restart_vpn`), whose command and settings it reuses. Only `name`,
+    /// `description`, `order`, `pinned` and `when` can be set on the alias.
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    /// Where the node is shown among its siblings: nodes with an `order` come before the
+    /// others, lower values first.
+    pub order: Option<i64>,
+    /// Whether the node is shown before all its siblings. Defaults to `false`.
+    pub pinned: Option<bool>,
+    /// Conditions that the machine must meet for the node (and its descendants) to be
+    /// available.
+    pub when: Option<WhenSchema>,
+    /// Variables which can be used as `${name}` in the `command`, `steps`, `cwd`, `env`,
+    /// `shell`, `description`, `confirm`, `preview_command`, `generator` and `child_command`
+    /// of the node and of its descendants (but not in their `script`).
+    pub vars: Option<BTreeMap<String, String>>,
+    /// The command to execute. Only used by commands (tables without children).
+    pub command: Option<String>,
+    /// Makes the node a group whose children are generated by this command when the group is
+    /// entered: every line it prints becomes a command running `child_command`.
+    pub generator: Option<String>,
+    /// The command of the children generated by `generator`, in which `{{item}}` is replaced
+    /// by the line that generated them. They also inherit the settings and `args` of the
+    /// group.
+    pub child_command: Option<String>,
+    /// For how many seconds the generated children are kept before `generator` runs again.
+    /// Defaults to 30.
+    pub generator_ttl: Option<u64>,
+    /// A command whose output is shown under the description of the node while it is
+    /// selected, e.g. `git log -5`. It runs in the background, in the `cwd` and with the `env`
+    /// of the node, and is stopped after 2 seconds.
+    pub preview_command: Option<String>,
+    /// Whether the command should be executed in an external terminal emulator.
+    pub external: Option<bool>,
+    /// Asks for a confirmation before executing the command: `true` to press `y`, or a word
+    /// which has to be typed.
+    pub confirm: Option<ConfirmSchema>,
+    /// The shell used to run the command, instead of the one of the `ship` table.
+    pub shell: Option<String>,
+    /// A (multi-line) script executed instead of `command`. The values of the `args` are
+    /// passed to it as positional arguments.
+    pub script: Option<String>,
+    /// Commands executed one after the other, instead of `command`. The run stops at the
+    /// first one which fails, unless `continue_on_error` is set.
+    pub steps: Option<Vec<String>>,
+    /// Whether the remaining `steps` are executed even after one of them fails. Defaults to
+    /// `false`.
+    pub continue_on_error: Option<bool>,
+    /// The program (with its arguments, e.g. `python3 -u`) that runs the `script`. Defaults
+    /// to the shell.
+    pub interpreter: Option<String>,
+    /// Prepended (followed by a space) to the `command` of the node and of its descendants.
+    pub command_prefix: Option<String>,
+    /// Arguments asked to the user before executing the command. Their values replace the
+    /// `{{name}}` placeholders in `command`.
+    pub args: Option<Vec<ArgSchema>>,
+    /// The directory in which the command is executed. `~` and environment variables are
+    /// expanded, and relative paths are relative to the directory of the file defining it.
+    pub cwd: Option<String>,
+    /// Environment variables set for the command, on top of Eddie's own environment. `~` and
+    /// environment variables are expanded in their values.
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+/// Either a single value or a list of them.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// The `confirm` key of a command (see [NodeSchema::confirm]).
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ConfirmSchema {
+    Enabled(bool),
+    Word(String),
+}
+
+/// The conditions of a node (see [NodeSchema::when]), which must all be met. See
+/// [crate::config_reader::conditions].
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WhenSchema {
+    /// The operating systems on which the node is available, e.g. `linux`, `macos` or
+    /// `windows`.
+    pub os: Option<OneOrMany>,
+    /// Globs matching the names of the hosts on which the node is available, e.g. `work-*`.
+    pub hostname: Option<OneOrMany>,
+    /// Environment variables which must be set.
+    pub env_set: Option<OneOrMany>,
+    /// Files or directories which must exist. Relative paths are relative to the file which
+    /// defines the node.
+    pub file_exists: Option<OneOrMany>,
+    /// Programs which must be on `PATH`.
+    pub on_path: Option<OneOrMany>,
+}
+
+/// An argument of a command (see [NodeSchema::args]).
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ArgSchema {
+    /// The name of the argument, as used in the `{{name}}` placeholders.
+    pub name: String,
+    /// The text shown when asking for the value. Defaults to the name of the argument.
+    pub prompt: Option<String>,
+    /// The value the argument starts with.
+    pub default: Option<String>,
+    /// If set, the value must be one of these.
+    pub choices: Option<Vec<String>>,
+    /// Whether the value can be left empty. Defaults to `false`.
+    pub required: Option<bool>,
+}
+
+impl From<ArgSchema> for CommandArg {
+    fn from(arg: ArgSchema) -> Self {
+        let name = arg.name;
+        let choices = arg.choices.unwrap_or_default();
+
+        CommandArg {
+            prompt: arg.prompt.unwrap_or_else(|| name.clone()),
+            // arguments with choices start with the first one selected
+            default: arg
+                .default
+                .or_else(|| choices.first().cloned())
+                .unwrap_or_default(),
+            name,
+            choices,
+            required: arg.required.unwrap_or(false),
+        }
+    }
+}
+
+/// The `ship` table, which holds the configuration of Eddie itself. Every key is optional,
+/// and missing keys take their value from the defaults (see [EddieConfig]).
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct ShipSchema {
+    /// The name of your Eddie instance.
+    pub name: Option<String>,
+    /// The application used to execute commands in an external terminal.
+    pub terminal_emulator: Option<String>,
+    /// The argument that tells the terminal emulator which command to execute.
+    pub terminal_emulator_command_arg: Option<String>,
+    /// The shell used to run commands (must be on your path).
+    pub shell: Option<String>,
+    /// Variables which can be used as `${name}` in the whole config, except in scripts.
+    pub vars: Option<BTreeMap<String, String>>,
+    /// How the groups and commands are sorted: `alphabetical` (the default) or `file`, which
+    /// keeps the order in which they're defined.
+    pub node_order: Option<NodeOrder>,
+    /// What to do with the nodes whose `when` conditions aren't met: `hide` them (the
+    /// default) or `grey_out` them.
+    pub unavailable_nodes: Option<UnavailableNodes>,
+    /// Which groups are generated from the task files (Makefiles, `package.json` scripts...)
+    /// found in the directory in which Eddie runs.
+    pub providers: Option<ProvidersSchema>,
+    /// The root directories of the projects whose previews and generators run by themselves,
+    /// and whose `ship` table can change the shell and the terminal emulator. `~` and
+    /// environment variables are expanded.
+    pub trusted_projects: Option<Vec<String>>,
+}
+
+/// The `providers` table of the `ship` table (see [crate::config_reader::providers]). Every
+/// provider is disabled unless it is set to `true`.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProvidersSchema {
+    /// A group with the targets of the `Makefile`.
+    pub make: Option<bool>,
+    /// A group with the scripts of the `package.json`.
+    pub npm: Option<bool>,
+    /// A group with the recipes of the `justfile`.
+    pub just: Option<bool>,
+    /// A group with the aliases of the `.cargo/config.toml`.
+    pub cargo: Option<bool>,
+}
+
+impl ProvidersSchema {
+    /// Overrides the fields of `providers` with the ones set in this table.
+    fn apply_to(self, providers: &mut Providers) {
+        let fields = [
+            (self.make, &mut providers.make),
+            (self.npm, &mut providers.npm),
+            (self.just, &mut providers.just),
+            (self.cargo, &mut providers.cargo),
+        ];
+
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+    }
+}
+
+impl ShipSchema {
+    /// Overrides the fields of `config` with the ones set in this table.
+    pub fn apply_to(self, config: &mut EddieConfig) {
+        if let Some(name) = self.name {
+            config.ship_name = name;
+        }
+        if let Some(terminal_emulator) = self.terminal_emulator {
+            config.terminal_emulator = terminal_emulator;
+        }
+        if let Some(command_arg) = self.terminal_emulator_command_arg {
+            config.terminal_emulator_command_arg = command_arg;
+        }
+        if let Some(shell) = self.shell {
+            config.shell = shell;
+        }
+        config.vars.extend(self.vars.unwrap_or_default());
+        if let Some(node_order) = self.node_order {
+            config.node_order = node_order;
+        }
+        if let Some(unavailable_nodes) = self.unavailable_nodes {
+            config.unavailable_nodes = unavailable_nodes;
+        }
+        if let Some(providers) = self.providers {
+            providers.apply_to(&mut config.providers);
+        }
+        let trusted_projects = self.trusted_projects.unwrap_or_default();
+        config
+            .trusted_projects
+            .extend(trusted_projects.into_iter().map(PathBuf::from));
+    }
+}
+
+/// An entry of the top level `include` array of a config file: a path (or glob) of the files
+/// to include, optionally mounted under a key path.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum IncludeSchema {
+    /// The path (or glob) of the files to include. `~` and environment variables are
+    /// expanded, and relative paths are relative to the including file.
+    Path(String),
+    Mounted {
+        /// The path (or glob) of the files to include.
+        path: String,
+        /// The key path under which the tables of the included files are placed, e.g.
+        /// `work.team`.
+        prefix: String,
+    },
+}
+
+/// Returns the object validation of the schema of `T`.
+fn object_schema<T: JsonSchema>() -> ObjectValidation {
+    let root = schemars::schema_for!(T);
+
+    root.schema.object.map(|e| *e).unwrap_or_default()
+}
+
+/// Returns the keys declared by `T`.
+pub fn declared_keys<T: JsonSchema>() -> Vec<String> {
+    object_schema::<T>().properties.into_keys().collect()
+}
+
+/// Returns the keys which `T` requires to be present.
+pub fn required_keys<T: JsonSchema>() -> Vec<String> {
+    object_schema::<T>().required.into_iter().collect()
+}
+
+/// Deserializes `T` from `table`. On failure the name of the offending key (if known) is
+/// returned together with the error message.
+pub fn deserialize_table<T: DeserializeOwned>(
+    table: Map<String, Value>,
+) -> Result<T, (Option<String>, String)> {
+    serde_path_to_error::deserialize(Value::Table(table)).map_err(|e| {
+        let key = e
+            .path()
+            .iter()
+            .next()
+            .map(|segment| segment.to_string().trim_start_matches('.').to_string());
+
+        (key, e.into_inner().to_string())
+    })
+}
+
+/// Returns the JSON Schema of a whole Eddie config file: an optional `ship` table and
+/// `include` array, plus any number of group/command tables, which can themselves be nested.
+pub fn config_json_schema() -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+
+    let node_ref = gen.subschema_for::<NodeSchema>();
+    let ship_ref = gen.subschema_for::<ShipSchema>();
+    let include_ref = gen.subschema_for::<Vec<IncludeSchema>>();
+
+    let mut definitions = gen.take_definitions();
+
+    // children of a node are all the keys which aren't declared by it
+    if let Some(Schema::Object(node)) = definitions.get_mut("NodeSchema") {
+        node.object().additional_properties = Some(Box::new(node_ref.clone()));
+    }
+
+    let mut root = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..Default::default()
+    };
+    root.metadata().title = Some("Eddie configuration".to_string());
+    root.object()
+        .properties
+        .insert("ship".to_string(), ship_ref);
+    root.object()
+        .properties
+        .insert("include".to_string(), include_ref);
+    root.object().additional_properties = Some(Box::new(node_ref));
+
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: root,
+        definitions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declared_and_required_keys() {
+        assert_eq!(
+            declared_keys::<NodeSchema>(),
+            vec![
+                "args",
+                "child_command",
+                "command",
+                "command_prefix",
+                "confirm",
+                "continue_on_error",
+                "cwd",
+                "description",
+                "env",
+                "external",
+                "generator",
+                "generator_ttl",
+                "interpreter",
+                "name",
+                "order",
+                "pinned",
+                "preview_command",
+                "ref",
+                "script",
+                "shell",
+                "steps",
+                "vars",
+                "when"
+            ]
+        );
+        assert_eq!(required_keys::<NodeSchema>(), vec!["name"]);
+        assert_eq!(required_keys::<ShipSchema>(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_deserialize_table_reports_the_offending_key() {
+        let table = "name = \"a\"\nexternal = \"yes\""
+            .parse::<Value>()
+            .unwrap()
+            .as_table()
+            .unwrap()
+            .clone();
+
+        let (key, _) = deserialize_table::<NodeSchema>(table).unwrap_err();
+
+        assert_eq!(key, Some("external".to_string()));
+    }
+
+    #[test]
+    fn test_partial_ship_table_keeps_defaults() {
+        let mut config = EddieConfig::default();
+
+        ShipSchema {
+            shell: Some("fish".to_string()),
+            ..Default::default()
+        }
+        .apply_to(&mut config);
+
+        assert_eq!(config.shell, "fish");
+        assert_eq!(config.ship_name, EddieConfig::default().ship_name);
+
+        // providers are overridden one by one too
+        for (make, npm) in [(Some(true), Some(true)), (None, Some(false))] {
+            ShipSchema {
+                providers: Some(ProvidersSchema {
+                    make,
+                    npm,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+            .apply_to(&mut config);
+        }
+
+        assert!(config.providers.make);
+        assert!(!config.providers.npm);
+    }
+
+    #[test]
+    fn test_json_schema_nests_nodes() {
+        let schema = serde_json::to_value(config_json_schema()).unwrap();
+
+        assert_eq!(
+            schema["additionalProperties"]["$ref"],
+            "#/definitions/NodeSchema"
+        );
+        assert_eq!(
+            schema["definitions"]["NodeSchema"]["additionalProperties"]["$ref"],
+            "#/definitions/NodeSchema"
+        );
+        assert_eq!(
+            schema["properties"]["ship"]["$ref"],
+            "#/definitions/ShipSchema"
+        );
+        assert_eq!(schema["properties"]["include"]["type"], "array");
+    }
+}
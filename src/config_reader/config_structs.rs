@@ -1,7 +1,45 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::config_reader::config_error::ConfigError;
+use crate::config_reader::config_merger::{MergeConflict, MergeWarning};
+use crate::config_reader::config_tree::ConfigTree;
+
 #[derive(Debug)]
 pub struct AppConfig {
     pub eddie_config: EddieConfig,
-    pub config_tree: ConfigNode,
+    pub config_tree: ConfigTree,
+    /// Keys defined by more than one config file (see [crate::config_reader::config_merger]).
+    pub merge_conflicts: Vec<MergeConflict>,
+    /// Config files that were skipped because they couldn't be read or parsed.
+    pub merge_warnings: Vec<MergeWarning>,
+    /// Problems found in the config which don't prevent Eddie from working, like unknown keys.
+    pub config_warnings: Vec<ConfigError>,
+    /// The config files and directories to watch to know when the config has to be
+    /// reloaded (see [crate::config_reader::config_watcher]).
+    pub watched_paths: Vec<PathBuf>,
+    /// The directory (or single file) the global config was read from.
+    pub config_location: PathBuf,
+    /// The config directory (or file) given with `--config`, if any. It is read again when the
+    /// config is reloaded.
+    pub config_override: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// Returns a human readable message for every conflict and warning found while
+    /// reading the config files.
+    pub fn issues(&self) -> Vec<String> {
+        self.merge_conflicts
+            .iter()
+            .map(|e| e.to_string())
+            .chain(self.merge_warnings.iter().map(|e| e.to_string()))
+            .chain(self.config_warnings.iter().map(|e| e.to_string()))
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -11,17 +49,168 @@ pub struct EddieConfig {
     pub terminal_emulator: String,
     pub terminal_emulator_command_arg: String,
     pub shell: String,
+    /// The variables of the `ship` table, see [crate::config_reader::expansion].
+    pub vars: BTreeMap<String, String>,
+    /// How the groups and commands are sorted.
+    pub node_order: NodeOrder,
+    /// What to do with the nodes which are not available on this machine.
+    pub unavailable_nodes: UnavailableNodes,
+    /// Which groups are generated from the task files of the working directory.
+    pub providers: Providers,
+    /// The root directories of the projects which are trusted, see [EddieConfig::trusts].
+    pub trusted_projects: Vec<PathBuf>,
 }
 
-#[derive(Debug)]
+impl EddieConfig {
+    /// Whether the project at `root` is trusted: its previews and generators run by
+    /// themselves, and its `ship` table can change the shell and the terminal emulator.
+    /// Projects come with the repositories they're in, so by default they aren't.
+    pub fn trusts(&self, root: &Path) -> bool {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+        self.trusted_projects
+            .iter()
+            .any(|e| e.canonicalize().unwrap_or_else(|_| e.clone()) == root)
+    }
+}
+
+/// The providers which generate groups from the task files found in the directory in which
+/// Eddie runs (see [crate::config_reader::providers]).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Providers {
+    /// `Makefile` targets.
+    pub make: bool,
+    /// `package.json` scripts.
+    pub npm: bool,
+    /// `justfile` recipes.
+    pub just: bool,
+    /// `.cargo/config.toml` aliases.
+    pub cargo: bool,
+}
+
+/// What to do with the nodes whose `when` conditions are not met.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnavailableNodes {
+    /// Remove them from the tree.
+    #[default]
+    Hide,
+    /// Show them greyed out, with the reason in the description pane. They can't be executed.
+    GreyOut,
+}
+
+/// How the nodes of each group are sorted, after the pinned ones and the ones with an
+/// explicit `order`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeOrder {
+    /// By name.
+    #[default]
+    Alphabetical,
+    /// In the order in which they're defined in the config files.
+    File,
+}
+
+impl Default for EddieConfig {
+    fn default() -> Self {
+        EddieConfig {
+            ship_name: String::from("Heart of Gold"),
+            terminal_emulator: String::from("alacritty"),
+            terminal_emulator_command_arg: String::from("--command"),
+            shell: String::from("bash"),
+            vars: BTreeMap::new(),
+            node_order: NodeOrder::Alphabetical,
+            unavailable_nodes: UnavailableNodes::Hide,
+            providers: Providers::default(),
+            trusted_projects: vec![],
+        }
+    }
+}
+
+/// What the user has to do to confirm that a command should be executed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Confirmation {
+    /// Press `y`.
+    Yes,
+    /// Type this word.
+    Word(String),
+}
+
+/// An argument of a command, whose value is asked to the user before executing the command
+/// and substituted into it through `{{name}}` placeholders (see [crate::executor::template]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandArg {
+    pub name: String,
+    /// The text shown to the user when asking for the value.
+    pub prompt: String,
+    pub default: String,
+    /// If not empty, the value must be one of these.
+    pub choices: Vec<String>,
+    /// Whether the value can be left empty.
+    pub required: bool,
+}
+
+/// The command whose output lines become the children of a group, see
+/// [crate::executor::generator].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generator {
+    /// The command which prints one item per line.
+    pub command: String,
+    /// The command of the children, in which `{{item}}` is replaced by their item.
+    pub child_command: String,
+    /// How long the children are kept before the generator runs again.
+    pub ttl: Duration,
+}
+
+#[derive(Debug, Default, Clone)]
 /// This is a structure which represents a single item in the config tree loaded from the TOML files.
 /// See the [crate::config_reader].
 pub struct ConfigNode {
+    /// The dotted path of the table that defines the node, e.g. `work.general.echo_hi`.
+    pub key_path: String,
     pub name: String,
     pub description: String,
     pub command: String,
     pub children: Option<Vec<ConfigNode>>,
     pub opens_external: bool,
+    /// Where the node is shown among its siblings, lower values first. See [NodeOrder].
+    pub order: Option<i64>,
+    /// Pinned nodes are shown before all their siblings.
+    pub pinned: bool,
+    /// Why the node is not available on this machine, if it isn't (see
+    /// [crate::config_reader::conditions]).
+    pub unavailable: Option<String>,
+    /// The arguments of the command, in the order in which they're declared.
+    pub args: Vec<CommandArg>,
+    /// If set, the user must confirm the command before it is executed.
+    pub confirm: Option<Confirmation>,
+    /// The shell used to run the command. If `None` then the one of the `ship` table is used.
+    pub shell: Option<String>,
+    /// A script executed instead of `command`, see [crate::executor::script].
+    pub script: Option<String>,
+    /// Commands executed one after the other instead of `command`.
+    pub steps: Vec<String>,
+    /// Whether the remaining `steps` are executed even after one of them fails.
+    pub continue_on_error: bool,
+    /// The program that runs `script`. If `None` then the shell is used.
+    pub interpreter: Option<String>,
+    /// The directory in which the command is executed, already expanded. If `None` then it
+    /// runs in Eddie's own working directory.
+    pub cwd: Option<PathBuf>,
+    /// Environment variables set for the command, with their values already expanded.
+    pub env: BTreeMap<String, String>,
+    /// If set, the node is a group whose children (after the ones defined in the config) are
+    /// generated when it is entered.
+    pub generator: Option<Generator>,
+    /// A command whose output is shown next to the description of the node, see
+    /// [crate::executor::preview].
+    pub preview_command: Option<String>,
+    /// For the children generated by a [Generator], the line which generated them. It is the
+    /// value of the `{{item}}` placeholder of their command.
+    pub generated_item: Option<String>,
+    /// Whether the node comes from a project which is not trusted (see
+    /// [EddieConfig::trusts]): its preview and its generator only run when asked for.
+    pub untrusted: bool,
 }
 
 impl ConfigNode {
@@ -29,7 +218,8 @@ impl ConfigNode {
         self.children.is_none()
     }
 
-    pub fn _is_valid(&self) -> bool {
-        self.children.is_some() || !self.command.is_empty()
+    /// Whether the node has something to execute: a `command`, a `script` or `steps`.
+    pub fn is_command(&self) -> bool {
+        !self.command.is_empty() || self.script.is_some() || !self.steps.is_empty()
     }
 }
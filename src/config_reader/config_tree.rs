@@ -0,0 +1,266 @@
+//! The config_tree module holds the tree of groups and commands once the config is read. The
+//! nodes are stored in an arena ([ConfigTree]) and refer to each other through [NodeId]s,
+//! so that the tree can be owned by whoever uses it, and replaced when the config is reloaded.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::config_reader::config_structs::ConfigNode;
+
+/// Identifies a node of a [ConfigTree]. IDs are only meaningful for the tree that returned
+/// them, nodes are matched across trees (e.g. after a reload) by their key path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug)]
+struct TreeEntry {
+    node: ConfigNode,
+    parent: Option<NodeId>,
+    /// `None` for commands, so that an empty group is still a group.
+    children: Option<Vec<NodeId>>,
+    /// How many of the `children` are defined in the config, the others were generated (see
+    /// [ConfigTree::set_generated_children]).
+    config_children: usize,
+    /// When the children of the group were last generated.
+    generated_at: Option<Instant>,
+}
+
+/// The tree of groups and commands, stored as an arena. The `children` of the nodes are
+/// moved into the arena when the tree is built, use [ConfigTree::children] to walk it.
+#[derive(Debug)]
+pub struct ConfigTree {
+    entries: Vec<TreeEntry>,
+    key_paths: HashMap<String, NodeId>,
+    /// The entries of the generated children which were replaced, which are reused by the
+    /// next inserted nodes so that generating children again doesn't grow the arena.
+    free: Vec<NodeId>,
+}
+
+impl ConfigTree {
+    /// Builds the tree whose root is `root`.
+    pub fn new(root: ConfigNode) -> ConfigTree {
+        let mut tree = ConfigTree {
+            entries: vec![],
+            key_paths: HashMap::new(),
+            free: vec![],
+        };
+        tree.insert(root, None);
+
+        tree
+    }
+
+    fn insert(&mut self, mut node: ConfigNode, parent: Option<NodeId>) -> NodeId {
+        let children = node.children.take();
+        let key_path = node.key_path.clone();
+        let entry = TreeEntry {
+            node,
+            parent,
+            children: None,
+            config_children: 0,
+            generated_at: None,
+        };
+
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.entries[id.0] = entry;
+                id
+            }
+            None => {
+                self.entries.push(entry);
+                NodeId(self.entries.len() - 1)
+            }
+        };
+        self.key_paths.insert(key_path, id);
+
+        if let Some(children) = children {
+            let child_ids: Vec<_> = children
+                .into_iter()
+                .map(|child| self.insert(child, Some(id)))
+                .collect();
+            self.entries[id.0].config_children = child_ids.len();
+            self.entries[id.0].children = Some(child_ids);
+        }
+
+        id
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &ConfigNode {
+        &self.entries[id.0].node
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    /// Returns the children of the group `id`, or `None` if it is a command.
+    pub fn children(&self, id: NodeId) -> Option<&[NodeId]> {
+        self.entries[id.0].children.as_deref()
+    }
+
+    pub fn is_group(&self, id: NodeId) -> bool {
+        self.entries[id.0].children.is_some()
+    }
+
+    /// Returns the node whose key path is `key_path`. The root's key path is empty.
+    pub fn find(&self, key_path: &str) -> Option<NodeId> {
+        self.key_paths.get(key_path).copied()
+    }
+
+    /// Whether the group `id` has a generator whose children are missing or older than its
+    /// TTL.
+    pub fn needs_generation(&self, id: NodeId) -> bool {
+        let entry = &self.entries[id.0];
+
+        match &entry.node.generator {
+            Some(generator) => entry
+                .generated_at
+                .is_none_or(|e| e.elapsed() >= generator.ttl),
+            None => false,
+        }
+    }
+
+    /// Replaces the generated children of the group `id` with `children`, which are placed
+    /// after the ones defined in the config. A child which is generated again keeps its ID,
+    /// while the IDs of the previous children which are gone are reused for other nodes.
+    /// Children whose key path is already used by another node (e.g. a child defined in the
+    /// config) are skipped.
+    pub fn set_generated_children(&mut self, id: NodeId, children: Vec<ConfigNode>) {
+        let config_children = self.entries[id.0].config_children;
+        let old_children = match &mut self.entries[id.0].children {
+            Some(c) => c.split_off(config_children),
+            None => return,
+        };
+
+        let mut old_ids = HashMap::new();
+        for old_child in old_children {
+            let key_path = &self.entries[old_child.0].node.key_path;
+            self.key_paths.remove(key_path);
+            old_ids.insert(key_path.clone(), old_child);
+        }
+
+        for child in children {
+            if self.key_paths.contains_key(&child.key_path) {
+                continue;
+            }
+            // inserting takes the last free ID, so the child gets its previous one back
+            if let Some(old_id) = old_ids.remove(&child.key_path) {
+                self.free.push(old_id);
+            }
+
+            let child_id = self.insert(child, Some(id));
+            if let Some(c) = &mut self.entries[id.0].children {
+                c.push(child_id);
+            }
+        }
+
+        self.free.extend(old_ids.into_values());
+        self.entries[id.0].generated_at = Some(Instant::now());
+    }
+
+    /// Returns the ancestors of `id`, from the root down to its parent.
+    pub fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ancestors = vec![];
+        let mut current = self.parent(id);
+
+        while let Some(parent) = current {
+            ancestors.insert(0, parent);
+            current = self.parent(parent);
+        }
+
+        ancestors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(key_path: &str, children: Option<Vec<ConfigNode>>) -> ConfigNode {
+        ConfigNode {
+            key_path: key_path.to_string(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tree_is_flattened() {
+        let tree = ConfigTree::new(node(
+            "",
+            Some(vec![
+                node(
+                    "a",
+                    Some(vec![node("a.b", None), node("a.c", Some(vec![]))]),
+                ),
+                node("d", None),
+            ]),
+        ));
+
+        let a = tree.find("a").unwrap();
+        let c = tree.find("a.c").unwrap();
+
+        assert_eq!(tree.children(tree.root()).unwrap().len(), 2);
+        assert_eq!(tree.node(tree.children(a).unwrap()[0]).key_path, "a.b");
+        assert!(tree.is_group(c));
+        assert_eq!(tree.children(c), Some(&[][..]));
+        assert!(!tree.is_group(tree.find("d").unwrap()));
+        assert!(tree.node(a).children.is_none());
+        assert_eq!(tree.ancestors(c), vec![tree.root(), a]);
+        assert_eq!(tree.find("e"), None);
+    }
+
+    #[test]
+    fn test_generated_children() {
+        use crate::config_reader::config_structs::Generator;
+        use std::time::Duration;
+
+        let mut group = node("g", Some(vec![node("g.static", None)]));
+        group.generator = Some(Generator {
+            command: "ls".to_string(),
+            child_command: "cat {{item}}".to_string(),
+            ttl: Duration::from_secs(60),
+        });
+        let mut tree = ConfigTree::new(node("", Some(vec![group])));
+        let g = tree.find("g").unwrap();
+
+        assert!(tree.needs_generation(g));
+        assert!(!tree.needs_generation(tree.root()));
+
+        tree.set_generated_children(g, vec![node("g.a", None), node("g.b", None)]);
+        assert!(!tree.needs_generation(g));
+        assert_eq!(tree.children(g).unwrap().len(), 3);
+
+        // generating them again replaces them, but keeps the ones defined in the config
+        tree.set_generated_children(g, vec![node("g.c", None)]);
+        let names: Vec<_> = tree
+            .children(g)
+            .unwrap()
+            .iter()
+            .map(|&e| tree.node(e).key_path.as_str())
+            .collect();
+        assert_eq!(names, vec!["g.static", "g.c"]);
+        assert_eq!(tree.find("g.a"), None);
+        assert_eq!(tree.parent(tree.find("g.c").unwrap()), Some(g));
+
+        // the children generated again keep their ID, and the arena doesn't grow
+        let c = tree.find("g.c").unwrap();
+        let entries = tree.entries.len();
+        for _ in 0..10 {
+            tree.set_generated_children(g, vec![node("g.d", None), node("g.c", None)]);
+        }
+        assert_eq!(tree.find("g.c"), Some(c));
+        assert_eq!(tree.entries.len(), entries);
+
+        // the children defined in the config can't be replaced
+        let static_id = tree.find("g.static").unwrap();
+        tree.set_generated_children(g, vec![node("g.static", None), node("g.e", None)]);
+        assert_eq!(tree.find("g.static"), Some(static_id));
+        assert_eq!(tree.node(static_id).key_path, "g.static");
+        assert_eq!(tree.children(g).unwrap().len(), 2);
+        assert_eq!(tree.find("g.c"), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! The config_watcher module tells when the config files change, so that the config can be
+//! reloaded while Eddie is running. It doesn't rely on filesystem notifications: the
+//! modification times of the watched files are polled instead, at most once per
+//! [POLL_INTERVAL] (see [ConfigWatcher::has_changed]).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use walkdir::WalkDir;
+
+/// How long to wait between two polls of the watched files, which walks the watched
+/// directories and reads the metadata of every file in them.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The modification time and size of every file found under the watched paths.
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    /// The files and directories (which are watched recursively) to watch. Paths which don't
+    /// exist are watched too, in case they're created.
+    paths: Vec<PathBuf>,
+    snapshot: Snapshot,
+    /// When the snapshot was taken.
+    polled_at: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> ConfigWatcher {
+        let snapshot = take_snapshot(&paths);
+
+        ConfigWatcher {
+            paths,
+            snapshot,
+            polled_at: Instant::now(),
+        }
+    }
+
+    /// Replaces the watched paths, e.g. after a reload that included new files.
+    pub fn watch(&mut self, paths: Vec<PathBuf>) {
+        self.snapshot = take_snapshot(&paths);
+        self.paths = paths;
+        self.polled_at = Instant::now();
+    }
+
+    /// Returns whether a file was created, modified or removed since the last poll (or since
+    /// the paths started being watched). The files are only polled again once [POLL_INTERVAL]
+    /// has passed, until then nothing is reported as changed.
+    pub fn has_changed(&mut self) -> bool {
+        self.has_changed_at(Instant::now())
+    }
+
+    fn has_changed_at(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.polled_at) < POLL_INTERVAL {
+            return false;
+        }
+
+        let snapshot = take_snapshot(&self.paths);
+        self.polled_at = now;
+        if snapshot == self.snapshot {
+            return false;
+        }
+
+        self.snapshot = snapshot;
+        true
+    }
+}
+
+fn take_snapshot(paths: &[PathBuf]) -> Snapshot {
+    paths
+        .iter()
+        .flat_map(|path| WalkDir::new(path).follow_links(true))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| {
+            let metadata = fs::metadata(e.path()).ok();
+            let modified = metadata.as_ref().and_then(|e| e.modified().ok());
+            let len = metadata.map(|e| e.len()).unwrap_or_default();
+
+            (e.into_path(), (modified, len))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_reader::test_util::tmp_dir;
+
+    #[test]
+    fn test_changes_are_detected() {
+        let dir = tmp_dir("watcher");
+        fs::write(dir.join("main.toml"), "a = 1").unwrap();
+
+        let mut watcher = ConfigWatcher::new(vec![dir.clone(), dir.join("missing.toml")]);
+        let start = watcher.polled_at;
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert!(!watcher.has_changed_at(at(1)));
+
+        // changes are only seen once the files are polled again
+        fs::write(dir.join("main.toml"), "a = 12").unwrap();
+        assert!(!watcher.has_changed_at(at(1)));
+        assert!(watcher.has_changed_at(at(2)));
+        assert!(!watcher.has_changed_at(at(3)));
+
+        fs::write(dir.join("other.toml"), "").unwrap();
+        assert!(watcher.has_changed_at(at(4)));
+
+        fs::remove_file(dir.join("other.toml")).unwrap();
+        assert!(watcher.has_changed_at(at(5)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+//! The expansion module expands `~` and environment variables in the values of the config
+//! which are paths or environment variables themselves, like the `cwd` and `env` keys of
+//! command tables. It also interpolates the user-defined `vars` ([interpolate_vars]).
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Replaces `$NAME` and `${NAME}` with the value of the environment variable `NAME`, using
+/// `lookup` to read it. Unset variables expand to an empty string, like they do in a shell,
+/// and `$$` is an escaped `$`.
+pub fn expand_env_vars_with<F>(value: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                expanded.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&e| e != '}').collect();
+                expanded.push_str(&lookup(&name).unwrap_or_default());
+            }
+            Some(&e) if e == '_' || e.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&e) = chars.peek() {
+                    if e != '_' && !e.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(e);
+                    chars.next();
+                }
+                expanded.push_str(&lookup(&name).unwrap_or_default());
+            }
+            _ => expanded.push('$'),
+        }
+    }
+
+    expanded
+}
+
+/// Replaces `${name}` with the value of the variable `name` as returned by `lookup`, or with
+/// `default` for `${name:-default}` if the variable is not defined. `$${` is an escaped `${`.
+/// Anything else, like `$name`, is left untouched. So is a `${name}` which is not defined and
+/// has no default, as the shell might expand it (e.g. a variable set by the command itself):
+/// the names of these variables are returned alongside the interpolated value.
+pub fn interpolate_with<F>(value: &str, lookup: F) -> (String, Vec<String>)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut interpolated = String::with_capacity(value.len());
+    let mut undefined = vec![];
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            interpolated.push_str("${");
+            rest = escaped;
+            continue;
+        }
+
+        let end = match rest.strip_prefix("${").and_then(|e| e.find('}')) {
+            Some(end) => end + 2,
+            None => {
+                interpolated.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let expression = &rest[2..end];
+        let (name, default) = match expression.find(":-") {
+            Some(idx) => (&expression[..idx], Some(&expression[idx + 2..])),
+            None => (expression, None),
+        };
+
+        match lookup(name).or_else(|| default.map(String::from)) {
+            Some(e) => interpolated.push_str(&e),
+            None => {
+                interpolated.push_str(&rest[..=end]);
+                undefined.push(name.to_string());
+            }
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    interpolated.push_str(rest);
+    (interpolated, undefined)
+}
+
+/// Interpolates the variables in `value` (see [interpolate_with]), which are looked up in
+/// `vars` first and then in the environment variables, using `env_var` to read them.
+pub fn interpolate_vars<F>(
+    value: &str,
+    vars: &BTreeMap<String, String>,
+    env_var: F,
+) -> (String, Vec<String>)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    interpolate_with(value, |name| {
+        vars.get(name).cloned().or_else(|| env_var(name))
+    })
+}
+
+/// Expands a leading `~` to the home directory of the user.
+pub fn expand_home(value: &str) -> PathBuf {
+    match (value.strip_prefix('~'), dirs::home_dir()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(value),
+    }
+}
+
+/// Expands `~` and the environment variables in the path `value`. If the resulting path is
+/// relative then it is resolved against `base_dir`.
+pub fn expand_path(value: &str, base_dir: Option<&Path>) -> PathBuf {
+    expand_path_with(value, base_dir, |name| env::var(name).ok())
+}
+
+/// Same as [expand_path], using `lookup` to read the environment variables.
+pub fn expand_path_with<F>(value: &str, base_dir: Option<&Path>, lookup: F) -> PathBuf
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let path = expand_home(&expand_env_vars_with(value, lookup));
+
+    match base_dir {
+        Some(base_dir) if path.is_relative() => base_dir.join(path),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "FOO" => Some("foo".to_string()),
+            "FOO_BAR" => Some("foobar".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        assert_eq!(expand_env_vars_with("$FOO/x", lookup), "foo/x");
+        assert_eq!(expand_env_vars_with("${FOO}_BAR", lookup), "foo_BAR");
+        assert_eq!(expand_env_vars_with("$FOO_BAR", lookup), "foobar");
+        assert_eq!(expand_env_vars_with("a$MISSING.b", lookup), "a.b");
+        assert_eq!(
+            expand_env_vars_with("$$FOO costs 5$", lookup),
+            "$FOO costs 5$"
+        );
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate_with("ssh ${FOO}@${HOST:-localhost}:$HOME", lookup),
+            ("ssh foo@localhost:$HOME".to_string(), vec![])
+        );
+        assert_eq!(
+            interpolate_with("${FOO:-x} $${FOO} ${ unclosed", lookup),
+            ("foo ${FOO} ${ unclosed".to_string(), vec![])
+        );
+        assert_eq!(
+            interpolate_with("${FOO} ${MISSING} ${OTHER:-} ${i}", lookup),
+            (
+                "foo ${MISSING}  ${i}".to_string(),
+                vec!["MISSING".to_string(), "i".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/src"), home.join("src"));
+        assert_eq!(expand_home("~other/src"), PathBuf::from("~other/src"));
+        assert_eq!(expand_home("/tmp"), PathBuf::from("/tmp"));
+    }
+
+    #[test]
+    fn test_relative_paths_are_resolved_against_the_base_dir() {
+        let base_dir = Path::new("/etc/eddie");
+
+        assert_eq!(
+            expand_path("scripts", Some(base_dir)),
+            PathBuf::from("/etc/eddie/scripts")
+        );
+        assert_eq!(expand_path("/srv", Some(base_dir)), PathBuf::from("/srv"));
+        assert_eq!(expand_path("scripts", None), PathBuf::from("scripts"));
+    }
+}
@@ -1,6 +1,18 @@
 //! The config reader module is in charge or parsing the TOML files that `eddie` uses
 //! as the source of the commands.
 
-mod config_aggregator;
+pub mod config_aggregator;
+pub mod conditions;
+pub mod config_checker;
+pub mod config_error;
+pub mod config_format;
+pub mod config_merger;
+pub mod config_schema;
+pub mod expansion;
 pub mod toml_parser;
 pub mod config_structs;
+pub mod config_tree;
+pub mod config_watcher;
+pub mod providers;
+#[cfg(test)]
+mod test_util;
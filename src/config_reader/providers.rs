@@ -0,0 +1,521 @@
+//! The providers module generates groups from the task files that a directory usually already
+//! has: the targets of a `Makefile`, the scripts of a `package.json`, the recipes of a
+//! `justfile` and the aliases of a `.cargo/config.toml`. Each provider is enabled in the
+//! `providers` table of the `ship` table ([Providers]).
+//!
+//! The generated commands run in the directory in which the task files were found, and their
+//! groups are shown next to the ones defined by the config files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use crate::config_reader::config_merger::MergeWarning;
+use crate::config_reader::config_structs::{CommandArg, ConfigNode, Providers};
+use crate::executor::template;
+
+/// A task found in a task file, from which a command is generated.
+#[derive(Debug, PartialEq)]
+struct Task {
+    name: String,
+    /// The documentation of the task, if the file has one for it.
+    description: Option<String>,
+    /// The parameters of the task, which are asked before running it.
+    args: Vec<CommandArg>,
+}
+
+impl Task {
+    fn new(name: &str, description: Option<String>) -> Task {
+        Task {
+            name: name.to_string(),
+            description,
+            args: vec![],
+        }
+    }
+}
+
+struct Provider {
+    /// The key of the provider in the `providers` table.
+    key: &'static str,
+    is_enabled: fn(&Providers) -> bool,
+    /// The name of the generated group.
+    name: &'static str,
+    /// The files that the provider reads, relative to the directory. The first one which
+    /// exists is used.
+    files: &'static [&'static str],
+    /// The program that runs the tasks.
+    program: &'static str,
+    parse: fn(&str) -> Result<Vec<Task>, String>,
+}
+
+const PROVIDERS: [Provider; 4] = [
+    Provider {
+        key: "make",
+        is_enabled: |e| e.make,
+        name: "make",
+        files: &["GNUmakefile", "makefile", "Makefile"],
+        program: "make",
+        parse: make_targets,
+    },
+    Provider {
+        key: "npm",
+        is_enabled: |e| e.npm,
+        name: "npm scripts",
+        files: &["package.json"],
+        program: "npm run",
+        parse: npm_scripts,
+    },
+    Provider {
+        key: "just",
+        is_enabled: |e| e.just,
+        name: "just",
+        files: &["justfile", "Justfile", ".justfile"],
+        program: "just",
+        parse: just_recipes,
+    },
+    Provider {
+        key: "cargo",
+        is_enabled: |e| e.cargo,
+        name: "cargo aliases",
+        files: &[".cargo/config.toml", ".cargo/config"],
+        program: "cargo",
+        parse: cargo_aliases,
+    },
+];
+
+impl Provider {
+    /// Builds the group of the tasks found in `file`, which run in `dir`.
+    fn group(&self, dir: &Path, file: &Path, tasks: Vec<Task>, shell: &str) -> ConfigNode {
+        let key_path = format!("provider:{}", self.key);
+
+        let children = tasks
+            .into_iter()
+            .map(|task| {
+                let mut command = format!("{} {}", self.program, quote_name(&task.name, shell));
+                for arg in &task.args {
+                    command.push_str(&format!(" {{{{{}}}}}", arg.name));
+                }
+
+                ConfigNode {
+                    key_path: format!("{}.{}", key_path, task.name),
+                    name: task.name,
+                    description: task
+                        .description
+                        .unwrap_or_else(|| format!("Runs `{}`", command)),
+                    command,
+                    args: task.args,
+                    cwd: Some(dir.to_path_buf()),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        ConfigNode {
+            key_path,
+            name: self.name.to_string(),
+            description: format!("Generated from {}", file.display()),
+            children: Some(children),
+            ..Default::default()
+        }
+    }
+}
+
+/// The groups generated by the providers.
+#[derive(Debug, Default)]
+pub struct ProvidedGroups {
+    pub nodes: Vec<ConfigNode>,
+    /// The files that the enabled providers read, whether they exist or not, so that the
+    /// groups can be generated again when they change.
+    pub files: Vec<PathBuf>,
+    /// The task files that couldn't be read or parsed.
+    pub warnings: Vec<MergeWarning>,
+}
+
+/// Generates a group for every provider enabled in `providers` whose task file is in `dir`.
+/// Groups without tasks are left out. `shell` is the shell used to quote the task names.
+pub fn provided_groups(dir: &Path, providers: &Providers, shell: &str) -> ProvidedGroups {
+    let mut groups = ProvidedGroups::default();
+
+    for provider in PROVIDERS.iter().filter(|e| (e.is_enabled)(providers)) {
+        let files: Vec<PathBuf> = provider.files.iter().map(|e| dir.join(e)).collect();
+        let file = files.iter().find(|e| e.is_file()).cloned();
+        groups.files.extend(files);
+
+        let file = match file {
+            Some(f) => f,
+            None => continue,
+        };
+
+        let tasks = fs::read_to_string(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|content| (provider.parse)(&content));
+
+        match tasks {
+            Ok(tasks) if !tasks.is_empty() => {
+                groups.nodes.push(provider.group(dir, &file, tasks, shell));
+            }
+            Ok(_) => {}
+            Err(message) => groups.warnings.push(MergeWarning { file, message }),
+        }
+    }
+
+    groups
+}
+
+/// Quotes `name` for `shell`, unless it is made of characters that don't need it.
+fn quote_name(name: &str, shell: &str) -> String {
+    let is_plain = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-.:/@+=".contains(c));
+
+    if is_plain {
+        name.to_string()
+    } else {
+        template::quote_for_shell(shell, name)
+    }
+}
+
+/// Returns the text of the comment on `line`, if it is one.
+fn comment_of(line: &str) -> Option<String> {
+    let comment = line.strip_prefix('#')?.trim_start_matches('#').trim();
+    Some(comment.to_string()).filter(|e| !e.is_empty())
+}
+
+/// Returns the explicit targets of a Makefile, documented by the comment before them or by a
+/// `## comment` after their prerequisites. Special targets (`.PHONY`...), pattern rules and
+/// targets which use variables are left out.
+fn make_targets(content: &str) -> Result<Vec<Task>, String> {
+    let mut targets: Vec<Task> = vec![];
+    let mut comment = None;
+
+    for line in content.lines() {
+        // recipes, and lines continuing a previous one
+        if line.starts_with(|c: char| c.is_whitespace()) || line.is_empty() {
+            comment = None;
+            continue;
+        }
+
+        if line.starts_with('#') {
+            comment = comment_of(line);
+            continue;
+        }
+
+        let description = comment.take();
+        let (names, rest) = match line.split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        // variable assignments (`a := b`, `a ?= b`...)
+        if names.contains('=') || rest.starts_with('=') || rest.starts_with(":=") {
+            continue;
+        }
+
+        let description = rest
+            .split_once("##")
+            .and_then(|(_, e)| Some(e.trim().to_string()).filter(|e| !e.is_empty()))
+            .or(description);
+
+        for name in names.split_whitespace() {
+            let is_special = name.starts_with('.') || name.contains(['%', '$']);
+            if !is_special && !targets.iter().any(|e| e.name == name) {
+                targets.push(Task::new(name, description.clone()));
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Returns the scripts of a `package.json`, described by their own command.
+fn npm_scripts(content: &str) -> Result<Vec<Task>, String> {
+    let package: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    let scripts = match package.get("scripts") {
+        Some(serde_json::Value::Object(scripts)) => scripts,
+        Some(_) => return Err("`scripts` is not an object".to_string()),
+        None => return Ok(vec![]),
+    };
+
+    Ok(scripts
+        .iter()
+        .map(|(name, script)| Task::new(name, script.as_str().map(|e| e.to_string())))
+        .collect())
+}
+
+/// Splits the parameters of a just recipe, keeping quoted default values together.
+fn split_params(params: &str) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut quote = None;
+
+    for c in params.chars() {
+        match (c, quote) {
+            (c, None) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    split.push(std::mem::take(&mut current));
+                }
+            }
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (c, _) => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        split.push(current);
+    }
+
+    split
+}
+
+/// Returns the byte offset of the first `:` of `line` which is outside of quotes.
+fn find_colon(line: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (idx, c) in line.char_indices() {
+        match (c, quote) {
+            (':', None) => return Some(idx),
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Returns the public recipes of a justfile, documented by the comment before them. Their
+/// parameters become arguments: the ones with a default value are optional.
+fn just_recipes(content: &str) -> Result<Vec<Task>, String> {
+    const SETTINGS: [&str; 5] = ["set ", "alias ", "export ", "import ", "mod "];
+
+    let mut recipes = vec![];
+    let mut comment = None;
+    let mut is_private = false;
+
+    for line in content.lines() {
+        if line.starts_with(|c: char| c.is_whitespace()) || line.is_empty() {
+            comment = None;
+            is_private = false;
+            continue;
+        }
+
+        if line.starts_with('#') {
+            comment = comment_of(line);
+            continue;
+        }
+
+        // attributes, which apply to the recipe below them
+        if line.starts_with('[') {
+            is_private |= line.contains("private");
+            continue;
+        }
+
+        let description = comment.take();
+        let private = std::mem::take(&mut is_private);
+
+        if SETTINGS.iter().any(|e| line.starts_with(e)) {
+            continue;
+        }
+
+        let header = match find_colon(line) {
+            // `name := value` is a variable
+            Some(colon) if !line[colon..].starts_with(":=") => &line[..colon],
+            _ => continue,
+        };
+
+        let mut params = split_params(header).into_iter();
+        let name = match params.next() {
+            Some(n) => n.trim_start_matches('@').to_string(),
+            None => continue,
+        };
+
+        if private || name.starts_with('_') {
+            continue;
+        }
+
+        let mut recipe = Task::new(&name, description);
+        for param in params {
+            let param = param.trim_start_matches(['+', '*', '$']);
+            let (param_name, default) = match param.split_once('=') {
+                Some((n, d)) => (n, Some(d.trim_matches(['\'', '"']).to_string())),
+                None => (param, None),
+            };
+
+            recipe.args.push(CommandArg {
+                name: param_name.to_string(),
+                prompt: param_name.to_string(),
+                required: default.is_none(),
+                default: default.unwrap_or_default(),
+                choices: vec![],
+            });
+        }
+
+        recipes.push(recipe);
+    }
+
+    Ok(recipes)
+}
+
+/// Returns the aliases of a `.cargo/config.toml`, described by the command they expand to.
+fn cargo_aliases(content: &str) -> Result<Vec<Task>, String> {
+    let config: Value = content
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+
+    let aliases = match config.get("alias") {
+        Some(Value::Table(aliases)) => aliases,
+        Some(_) => return Err("`alias` is not a table".to_string()),
+        None => return Ok(vec![]),
+    };
+
+    Ok(aliases
+        .iter()
+        .map(|(name, expansion)| {
+            let expansion = match expansion {
+                Value::String(s) => Some(s.clone()),
+                Value::Array(a) => Some(
+                    a.iter()
+                        .filter_map(|e| e.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                _ => None,
+            };
+
+            Task::new(name, expansion.map(|e| format!("cargo {}", e)))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_reader::test_util::write_tmp_files;
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_make_targets() {
+        let targets = make_targets(
+            r#"
+CC := gcc
+PREFIX ?= /usr/local
+
+.PHONY: build test
+
+# Builds everything
+build: src/main.c
+	$(CC) -o app src/main.c
+
+test lint: build ## Checks the code
+	./app --test
+
+%.o: %.c
+	$(CC) -c $<
+
+$(OUT): build
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(names(&targets), vec!["build", "test", "lint"]);
+        assert_eq!(targets[0].description.as_deref(), Some("Builds everything"));
+        assert_eq!(targets[2].description.as_deref(), Some("Checks the code"));
+    }
+
+    #[test]
+    fn test_just_recipes() {
+        let recipes = just_recipes(
+            r#"
+set shell := ["bash", "-c"]
+version := "1.0"
+
+# Runs the tests
+test:
+    cargo test
+
+[private]
+helper:
+    echo helper
+
+_hidden:
+    echo hidden
+
+@deploy env target='web app' +flags: test
+    ./deploy.sh {{env}} {{target}} {{flags}}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(names(&recipes), vec!["test", "deploy"]);
+        assert_eq!(recipes[0].description.as_deref(), Some("Runs the tests"));
+
+        let args = &recipes[1].args;
+        assert_eq!(args.len(), 3);
+        assert!(args[0].required);
+        assert_eq!(
+            (args[1].name.as_str(), args[1].default.as_str()),
+            ("target", "web app")
+        );
+        assert!(!args[1].required);
+        assert_eq!(args[2].name, "flags");
+    }
+
+    #[test]
+    fn test_npm_scripts_and_cargo_aliases() {
+        let scripts =
+            npm_scripts(r#"{ "name": "app", "scripts": { "build:prod": "vite build" } }"#).unwrap();
+        assert_eq!(names(&scripts), vec!["build:prod"]);
+        assert_eq!(scripts[0].description.as_deref(), Some("vite build"));
+        assert!(npm_scripts("{ nope").is_err());
+
+        let aliases = cargo_aliases("[alias]\nb = \"build\"\nxt = [\"test\", \"--all\"]").unwrap();
+        assert_eq!(names(&aliases), vec!["b", "xt"]);
+        assert_eq!(aliases[1].description.as_deref(), Some("cargo test --all"));
+    }
+
+    #[test]
+    fn test_provided_groups() {
+        let dir = write_tmp_files(
+            "providers",
+            &[
+                ("Makefile", "build:\n\techo build\n"),
+                ("package.json", "{ \"scripts\": 1 }"),
+                (".cargo/config.toml", "[alias]\nb = \"build\"\n"),
+                ("justfile", "test:\n    cargo test\n"),
+            ],
+        );
+
+        let providers = Providers {
+            make: true,
+            npm: true,
+            just: false,
+            cargo: true,
+        };
+        let groups = provided_groups(&dir, &providers, "bash");
+
+        let group_names: Vec<_> = groups.nodes.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(group_names, vec!["make", "cargo aliases"]);
+        assert_eq!(groups.warnings.len(), 1);
+        assert!(!groups.files.contains(&dir.join("justfile")));
+
+        let alias = &groups.nodes[1].children.as_ref().unwrap()[0];
+        assert_eq!(alias.key_path, "provider:cargo.b");
+        assert_eq!(alias.command, "cargo b");
+        // cargo aliases run from the directory that contains `.cargo`
+        assert_eq!(alias.cwd.as_deref(), Some(dir.as_path()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+//! Helpers for the tests of the config reader which need real files on disk.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// Creates a new empty directory in the temporary directory of the system. Its name starts
+/// with `eddie-test-{name}-` and ends with a random suffix, so that tests running at the same
+/// time (or leftovers of previous runs) never share it.
+pub fn tmp_dir(name: &str) -> PathBuf {
+    let random_suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let dir = env::temp_dir().join(format!("eddie-test-{}-{}", name, random_suffix));
+
+    fs::create_dir(&dir).unwrap();
+
+    dir
+}
+
+/// Writes `files` (relative path and content) to a new directory created by [tmp_dir], and
+/// returns the directory.
+pub fn write_tmp_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = tmp_dir(name);
+
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    dir
+}
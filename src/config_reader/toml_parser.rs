@@ -1,127 +1,1830 @@
+use std::any::TypeId;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use toml::{value::Map, Value};
 
+use crate::config_reader::conditions;
 use crate::config_reader::config_aggregator;
+use crate::config_reader::config_error::{ConfigError, ConfigErrorKind, ConfigErrors};
+use crate::config_reader::config_format::ConfigFormat;
+use crate::config_reader::config_merger::{self, MergedConfig};
+use crate::config_reader::config_schema::{self, ArgSchema, ConfirmSchema, NodeSchema, ShipSchema};
 use crate::config_reader::config_structs::*;
+use crate::config_reader::config_tree::ConfigTree;
+use crate::config_reader::expansion;
+use crate::config_reader::providers::{self, ProvidedGroups};
+use crate::executor::template;
 
-fn get_sub_table_keys(val: &Value) -> Option<Vec<&String>> {
-    let table = val.as_table()?;
-    let keys = table.keys().collect::<Vec<_>>();
+/// All the problems found while parsing the config. Errors prevent Eddie from starting,
+/// while warnings are only shown to the user.
+#[derive(Debug, Default)]
+struct Diagnostics {
+    errors: Vec<ConfigError>,
+    warnings: Vec<ConfigError>,
+}
 
-    let mut table_keys = Vec::new();
+/// The keys declared and required by a schema (see [config_schema::declared_keys] and
+/// [config_schema::required_keys]).
+struct SchemaKeys {
+    declared: Vec<String>,
+    required: Vec<String>,
+}
+
+/// Keeps track of the merged config that is being parsed and of all the problems found
+/// while parsing it, so that every problem can be reported at once.
+struct ParseContext<'a> {
+    merged: &'a MergedConfig,
+    diagnostics: &'a mut Diagnostics,
+    /// Prefix added to the key path of every parsed node, used to keep the nodes of
+    /// different projects apart.
+    key_prefix: &'a str,
+    /// The aliases found so far, which are resolved once all the nodes are parsed.
+    aliases: Vec<AliasRef>,
+    /// How the nodes are sorted, see [sort_nodes].
+    node_order: NodeOrder,
+    /// What to do with the nodes whose conditions aren't met, see [hide_unavailable_nodes].
+    unavailable_nodes: UnavailableNodes,
+    /// The keys of the schemas used so far, since generating a schema is expensive and the
+    /// same ones are used for every table.
+    schema_keys: HashMap<TypeId, Rc<SchemaKeys>>,
+    /// Reads the environment variables, which the tests replace so that they don't depend on
+    /// (or have to change) the environment of the process.
+    env_var: fn(&str) -> Option<String>,
+}
 
-    for key in keys {
-        let item = table.get(key)?;
-        if let Some(_i) = item.as_table() {
-            table_keys.push(key);
+impl<'a> ParseContext<'a> {
+    fn new(
+        merged: &'a MergedConfig,
+        diagnostics: &'a mut Diagnostics,
+        key_prefix: &'a str,
+    ) -> ParseContext<'a> {
+        ParseContext {
+            merged,
+            diagnostics,
+            key_prefix,
+            aliases: vec![],
+            node_order: NodeOrder::default(),
+            unavailable_nodes: UnavailableNodes::default(),
+            schema_keys: HashMap::new(),
+            env_var: |name| env::var(name).ok(),
         }
     }
 
-    if !table_keys.is_empty() {
-        Some(table_keys)
+    /// Returns the keys of the schema `T`, which are only computed the first time.
+    fn schema_keys<T: JsonSchema + 'static>(&mut self) -> Rc<SchemaKeys> {
+        self.schema_keys
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Rc::new(SchemaKeys {
+                    declared: config_schema::declared_keys::<T>(),
+                    required: config_schema::required_keys::<T>(),
+                })
+            })
+            .clone()
+    }
+
+    /// Creates a [ConfigError] for `key` of the table at `table_path`, finding out which
+    /// file and line it comes from.
+    fn config_error(&self, table_path: &str, key: &str, kind: ConfigErrorKind) -> ConfigError {
+        let key_path = join_key_path(table_path, key);
+
+        let file = self.merged.origin_of(&key_path).cloned();
+        let location = file.as_ref().and_then(|f| {
+            let content = self.merged.source_of(f)?;
+            let format = ConfigFormat::from_path(f).unwrap_or(ConfigFormat::Toml);
+            format.locate_key(
+                content,
+                self.merged.local_table_path(f, table_path),
+                Some(key),
+            )
+        });
+
+        ConfigError {
+            file,
+            key_path,
+            location,
+            kind,
+        }
+    }
+
+    fn error(&mut self, table_path: &str, key: &str, kind: ConfigErrorKind) {
+        let error = self.config_error(table_path, key, kind);
+        self.diagnostics.errors.push(error);
+    }
+
+    fn warning(&mut self, table_path: &str, key: &str, kind: ConfigErrorKind) {
+        let warning = self.config_error(table_path, key, kind);
+        self.diagnostics.warnings.push(warning);
+    }
+
+    /// Interpolates `vars` and the environment variables in `value` (see
+    /// [expansion::interpolate_vars]). The variables which are not defined are left for the
+    /// shell to expand, so this is for the values which are run by the shell.
+    fn interpolate(&self, value: String, vars: &BTreeMap<String, String>) -> String {
+        expansion::interpolate_vars(&value, vars, self.env_var).0
+    }
+
+    /// Interpolates `vars` and the environment variables in `value`, the value of `key` of the
+    /// table at `table_path`, which the shell never expands (e.g. `cwd`). An error is recorded
+    /// for every variable which is not defined.
+    fn interpolate_strict(
+        &mut self,
+        table_path: &str,
+        key: &str,
+        value: String,
+        vars: &BTreeMap<String, String>,
+    ) -> String {
+        let (interpolated, undefined) = expansion::interpolate_vars(&value, vars, self.env_var);
+        for name in undefined {
+            self.error(table_path, key, ConfigErrorKind::UndefinedVariable(name));
+        }
+
+        interpolated
+    }
+
+    /// Deserializes the keys of `table` which are declared by the schema `T` (see
+    /// [config_schema]), recording an error for every missing or invalid key. The remaining
+    /// keys which hold tables are returned as children, and the rest are reported as unknown.
+    fn parse_declared<'v, T: JsonSchema + DeserializeOwned + 'static>(
+        &mut self,
+        table: &'v Map<String, Value>,
+        table_path: &str,
+    ) -> (Option<T>, Vec<(&'v String, &'v Value)>) {
+        let schema_keys = self.schema_keys::<T>();
+
+        let mut declared = Map::new();
+        let mut children = vec![];
+
+        for (key, value) in table {
+            if schema_keys.declared.contains(key) {
+                declared.insert(key.clone(), value.clone());
+            } else if value.is_table() {
+                children.push((key, value));
+            } else {
+                self.warning(table_path, key, ConfigErrorKind::UnknownKey(key.clone()));
+            }
+        }
+
+        let missing_keys: Vec<_> = schema_keys
+            .required
+            .iter()
+            .filter(|e| !declared.contains_key(e.as_str()))
+            .cloned()
+            .collect();
+
+        for key in &missing_keys {
+            self.error(table_path, key, ConfigErrorKind::MissingKey(key.clone()));
+        }
+
+        if !missing_keys.is_empty() {
+            return (None, children);
+        }
+
+        match config_schema::deserialize_table::<T>(declared) {
+            Ok(parsed) => (Some(parsed), children),
+            Err((key, message)) => {
+                let key = key.unwrap_or_default();
+                self.error(
+                    table_path,
+                    &key,
+                    ConfigErrorKind::InvalidValue {
+                        key: key.clone(),
+                        message,
+                    },
+                );
+
+                (None, children)
+            }
+        }
+    }
+}
+
+/// The settings that a group passes down to all its descendants. Each of them is used unless
+/// the descendant sets its own value (the `env` and `vars` tables are merged instead).
+#[derive(Debug, Default, Clone)]
+struct NodeDefaults {
+    vars: BTreeMap<String, String>,
+    shell: Option<String>,
+    interpreter: Option<String>,
+    cwd: Option<PathBuf>,
+    env: BTreeMap<String, String>,
+    external: Option<bool>,
+    command_prefix: Option<String>,
+    /// Why the ancestors of the node are not available, if they aren't.
+    unavailable: Option<String>,
+}
+
+/// The keys that an alias can set, besides `ref`. Everything else comes from the node it
+/// refers to.
+const ALIAS_KEYS: [&str; 5] = ["name", "description", "order", "pinned", "when"];
+
+/// A node which reuses the command and settings of another node through the `ref` key.
+#[derive(Debug)]
+struct AliasRef {
+    /// The path of the alias' table, used to report errors.
+    table_path: String,
+    /// The key path of the alias' node.
+    key_path: String,
+    /// The key path of the node it refers to, as written in the config.
+    target: String,
+    /// Whether the alias sets its own name and description.
+    has_name: bool,
+    has_description: bool,
+}
+
+/// Returns the dotted path of `key` inside the table at `table_path`.
+fn join_key_path(table_path: &str, key: &str) -> String {
+    if table_path.is_empty() {
+        key.to_string()
     } else {
-        None
+        format!("{}.{}", table_path, key)
     }
 }
 
-fn parse_nodes(val: &Value) -> ConfigNode {
-    let table = val.as_table().unwrap();
+/// Parses the value at `key` of the table at `parent_path` into a [ConfigNode], resolving
+/// the settings it inherits from its ancestors (`defaults`). Any problem found is recorded in
+/// `ctx`, and parsing carries on as far as possible so that all of them are found in one go.
+fn parse_nodes(
+    val: &Value,
+    parent_path: &str,
+    key: &str,
+    defaults: &NodeDefaults,
+    ctx: &mut ParseContext,
+) -> Option<ConfigNode> {
+    let key_path = &join_key_path(parent_path, key);
 
-    let name = table.get("name").unwrap().as_str().unwrap();
-    let description = match table.get("description") {
-        None => "NO DESCRIPTION PROVIDED",
-        Some(e) => e.as_str().unwrap(),
+    let table = match val.as_table() {
+        Some(t) => t,
+        None => {
+            ctx.error(
+                parent_path,
+                key,
+                ConfigErrorKind::NotATable {
+                    found: val.type_str(),
+                },
+            );
+            return None;
+        }
     };
 
-    let command = if let Some(command) = table.get("command") {
-        command.as_str().unwrap()
+    // aliases can leave out the name, which is then taken from the node they refer to
+    let has_name = table.contains_key("name");
+    let alias_table;
+    let table = if table.contains_key("ref") && !has_name {
+        let mut t = table.clone();
+        t.insert("name".to_string(), Value::String(String::new()));
+        alias_table = t;
+        &alias_table
     } else {
-        ""
+        table
     };
 
-    let opens_external = if let Some(opens_external) = table.get("external") {
-        opens_external.as_bool().unwrap()
-    } else {
-        false
+    let (node, sub_tables) = ctx.parse_declared::<NodeSchema>(table, key_path);
+    let mut node = node.unwrap_or_default();
+
+    let is_alias = node.reference.is_some();
+    if let Some(target) = node.reference.take() {
+        ctx.aliases.push(AliasRef {
+            table_path: key_path.clone(),
+            key_path: join_key_path(ctx.key_prefix, key_path),
+            target,
+            has_name,
+            has_description: node.description.is_some(),
+        });
+
+        // `command`, `script` and `steps` are reported as conflicting with `ref` below
+        let schema_keys = ctx.schema_keys::<NodeSchema>();
+        let overrides = table.iter().filter(|&(k, v)| {
+            (schema_keys.declared.contains(k) || v.is_table())
+                && !ALIAS_KEYS.contains(&k.as_str())
+                && !["ref", "command", "script", "steps"].contains(&k.as_str())
+        });
+        for (key, _) in overrides {
+            ctx.error(key_path, key, ConfigErrorKind::AliasOverride(key.clone()));
+        }
+    }
+
+    // variables can refer to the ones defined by the ancestors of the node
+    let mut vars = defaults.vars.clone();
+    for (name, value) in node.vars.take().unwrap_or_default() {
+        let value = ctx.interpolate(value, &defaults.vars);
+        vars.insert(name, value);
+    }
+
+    // relative directories are relative to the file which defines them, not to Eddie's cwd
+    let cwd = node.cwd.take().map(|cwd| {
+        let cwd = ctx.interpolate_strict(key_path, "cwd", cwd, &vars);
+        let defining_file = ctx.merged.origin_of(&join_key_path(key_path, "cwd"));
+        expansion::expand_path_with(&cwd, defining_file.and_then(|f| f.parent()), ctx.env_var)
+    });
+    let mut env = defaults.env.clone();
+    for (name, value) in node.env.take().unwrap_or_default() {
+        let value = ctx.interpolate_strict(key_path, "env", value, &vars);
+        let value = expansion::expand_home(&expansion::expand_env_vars_with(&value, ctx.env_var));
+        env.insert(name, value.to_string_lossy().to_string());
+    }
+    // descendants of an unavailable group are unavailable too
+    let unavailable = node
+        .when
+        .take()
+        .and_then(|when| {
+            let defining_file = ctx.merged.origin_of(&join_key_path(key_path, "when"));
+            conditions::unmet_condition(when, defining_file.and_then(|f| f.parent()))
+        })
+        .or_else(|| defaults.unavailable.clone());
+    let shell = node
+        .shell
+        .take()
+        .map(|shell| ctx.interpolate_strict(key_path, "shell", shell, &vars));
+
+    let defaults = NodeDefaults {
+        vars,
+        shell: shell.or_else(|| defaults.shell.clone()),
+        interpreter: node
+            .interpreter
+            .take()
+            .or_else(|| defaults.interpreter.clone()),
+        cwd: cwd.or_else(|| defaults.cwd.clone()),
+        env,
+        external: node.external.or(defaults.external),
+        command_prefix: node
+            .command_prefix
+            .take()
+            .or_else(|| defaults.command_prefix.clone()),
+        unavailable,
     };
 
-    let sub_tables = get_sub_table_keys(val);
-    let mut parsed_subtables: Vec<ConfigNode> = sub_tables
+    // a node runs either a command, a script or a list of steps
+    let executable_keys: Vec<&str> = [
+        ("ref", is_alias),
+        ("command", node.command.is_some()),
+        ("script", node.script.is_some()),
+        ("steps", node.steps.is_some()),
+    ]
+    .iter()
+    .filter(|&&(_, is_set)| is_set)
+    .map(|&(key, _)| key)
+    .collect();
+
+    for key in executable_keys.iter().skip(1) {
+        ctx.error(
+            key_path,
+            key,
+            ConfigErrorKind::ConflictingKeys {
+                key: key.to_string(),
+                other: executable_keys[0].to_string(),
+            },
+        );
+    }
+
+    let with_prefix = |command: String| match &defaults.command_prefix {
+        Some(prefix) => format!("{} {}", prefix, command),
+        None => command,
+    };
+    let command = match node.command.take().map(with_prefix) {
+        Some(command) => ctx.interpolate(command, &defaults.vars),
+        None => String::new(),
+    };
+    let steps: Vec<String> = node
+        .steps
+        .take()
         .unwrap_or_default()
-        .iter()
-        .map(|&e| parse_nodes(table.get(e).unwrap()))
+        .into_iter()
+        .map(|step| ctx.interpolate(with_prefix(step), &defaults.vars))
         .collect();
+    let confirm = match node.confirm.take() {
+        Some(ConfirmSchema::Enabled(true)) => Some(Confirmation::Yes),
+        Some(ConfirmSchema::Word(word)) => {
+            let word = ctx.interpolate_strict(key_path, "confirm", word, &defaults.vars);
+            if word.trim().is_empty() {
+                ctx.error(
+                    key_path,
+                    "confirm",
+                    ConfigErrorKind::InvalidValue {
+                        key: "confirm".to_string(),
+                        message: "the word to type can't be empty".to_string(),
+                    },
+                );
+                None
+            } else {
+                Some(Confirmation::Word(word))
+            }
+        }
+        Some(ConfirmSchema::Enabled(false)) | None => None,
+    };
+    let description = node
+        .description
+        .take()
+        .map(|e| ctx.interpolate_strict(key_path, "description", e, &defaults.vars));
+    let preview_command = node
+        .preview_command
+        .take()
+        .map(|e| ctx.interpolate(e, &defaults.vars));
 
-    // sorting here will ensure that all levels of the nodes are sorted
-    parsed_subtables.sort_by_key(|e| e.name.to_string());
+    // a generator and the command of its children only make sense together
+    let generator = match (node.generator.take(), node.child_command.take()) {
+        (Some(generator), Some(child_command)) => Some(Generator {
+            command: ctx.interpolate(generator, &defaults.vars),
+            child_command: ctx.interpolate(with_prefix(child_command), &defaults.vars),
+            ttl: Duration::from_secs(node.generator_ttl.unwrap_or(30)),
+        }),
+        (generator, child_command) => {
+            let missing = match (generator, child_command) {
+                (Some(_), None) => Some("child_command"),
+                (None, Some(_)) => Some("generator"),
+                _ => None,
+            };
+            if let Some(missing) = missing {
+                ctx.error(
+                    key_path,
+                    missing,
+                    ConfigErrorKind::MissingKey(missing.to_string()),
+                );
+            }
+            None
+        }
+    };
 
-    ConfigNode {
-        name: name.to_string(),
-        description: description.to_string(),
-        command: command.to_string(),
-        opens_external,
-        children: if parsed_subtables.is_empty() {
+    let templates = std::iter::once(("command", command.as_str()))
+        .chain(steps.iter().map(|e| ("steps", e.as_str())))
+        .chain(
+            generator
+                .iter()
+                .map(|e| ("child_command", e.child_command.as_str())),
+        )
+        .collect::<Vec<_>>();
+    let args = parse_args(
+        node.args.take().unwrap_or_default(),
+        &templates,
+        key_path,
+        ctx,
+    );
+
+    // the children are sorted once the whole tree is parsed (see [sort_nodes]), and those of
+    // an alias have already been reported
+    let parsed_subtables: Vec<ConfigNode> = sub_tables
+        .iter()
+        .filter(|_| !is_alias)
+        .filter_map(|&(k, v)| parse_nodes(v, key_path, k, &defaults, ctx))
+        .collect();
+
+    Some(ConfigNode {
+        key_path: join_key_path(ctx.key_prefix, key_path),
+        name: node.name,
+        description: description.unwrap_or_else(|| "NO DESCRIPTION PROVIDED".to_string()),
+        command,
+        opens_external: defaults.external.unwrap_or(false),
+        order: node.order,
+        pinned: node.pinned.unwrap_or(false),
+        unavailable: defaults.unavailable,
+        args,
+        confirm,
+        shell: defaults.shell,
+        // scripts aren't interpolated, since `${1:-default}` and the like are for the
+        // interpreter, e.g. to read the arguments of the command
+        script: node.script,
+        steps,
+        continue_on_error: node.continue_on_error.unwrap_or(false),
+        interpreter: defaults.interpreter,
+        cwd: defaults.cwd,
+        env: defaults.env,
+        // groups with a generator are groups even before their children are generated
+        children: if parsed_subtables.is_empty() && generator.is_none() {
             None
         } else {
             Some(parsed_subtables)
         },
+        generator,
+        preview_command,
+        generated_item: None,
+        // the nodes of the untrusted projects are marked once they're parsed (see
+        // [parse_project_config])
+        untrusted: false,
+    })
+}
+
+/// Turns the declared `args` of the node at `key_path` into [CommandArg]s, checking that they
+/// are consistent among themselves and with the placeholders used in `templates` (pairs of
+/// key and template, e.g. `command` and its value).
+fn parse_args(
+    arg_schemas: Vec<ArgSchema>,
+    templates: &[(&str, &str)],
+    key_path: &str,
+    ctx: &mut ParseContext,
+) -> Vec<CommandArg> {
+    let args: Vec<CommandArg> = arg_schemas.into_iter().map(CommandArg::from).collect();
+
+    for (idx, arg) in args.iter().enumerate() {
+        let mut invalid = |message: &str| {
+            ctx.error(
+                key_path,
+                "args",
+                ConfigErrorKind::InvalidArgument {
+                    name: arg.name.clone(),
+                    message: message.to_string(),
+                },
+            )
+        };
+
+        if args[..idx].iter().any(|e| e.name == arg.name) {
+            invalid("declared more than once");
+        }
+
+        if !arg.choices.is_empty() && !arg.choices.contains(&arg.default) {
+            invalid("the default value is not one of the choices");
+        }
+    }
+
+    for &(key, command) in templates {
+        for placeholder in template::find_placeholders(command) {
+            // the item of the generated children (see [Generator])
+            let is_item = key == "child_command" && placeholder == "item";
+
+            if !is_item && !args.iter().any(|e| e.name == placeholder) {
+                ctx.error(
+                    key_path,
+                    key,
+                    ConfigErrorKind::UndeclaredArgument(placeholder.to_string()),
+                );
+            }
+        }
     }
+
+    args
 }
 
-fn get_eddie_config(root_table: &Map<String, Value>) -> Option<EddieConfig> {
-    // check if we have the node for the root config
-    let ship_table = root_table.get("ship")?;
+/// Parses the `ship` table of `root_table`, if there is one. Its variables are interpolated
+/// using the ones in `base_vars` (the variables of the config it is layered on top of).
+fn get_ship_config(
+    root_table: &Map<String, Value>,
+    base_vars: &BTreeMap<String, String>,
+    ctx: &mut ParseContext,
+) -> Option<ShipSchema> {
+    let ship_table = match root_table.get("ship")? {
+        Value::Table(t) => t,
+        other => {
+            ctx.error(
+                "",
+                "ship",
+                ConfigErrorKind::NotATable {
+                    found: other.type_str(),
+                },
+            );
+            return None;
+        }
+    };
 
-    let get_att = |attr| Some(ship_table.get(attr)?.as_str()?.to_string());
+    let (ship, sub_tables) = ctx.parse_declared::<ShipSchema>(ship_table, "ship");
+
+    for (key, _) in sub_tables {
+        ctx.warning("ship", key, ConfigErrorKind::UnknownKey(key.clone()));
+    }
 
-    Some(EddieConfig {
-        ship_name: get_att("name")?,
-        terminal_emulator: get_att("terminal_emulator")?,
-        terminal_emulator_command_arg: get_att("terminal_emulator_command_arg")?,
-        shell: get_att("shell")?,
+    let mut ship = ship?;
+
+    let mut vars = base_vars.clone();
+    for (name, value) in ship.vars.take().unwrap_or_default() {
+        let value = ctx.interpolate(value, base_vars);
+        vars.insert(name, value);
+    }
+
+    ship.terminal_emulator = ship
+        .terminal_emulator
+        .map(|e| ctx.interpolate_strict("ship", "terminal_emulator", e, &vars));
+    ship.shell = ship
+        .shell
+        .map(|e| ctx.interpolate_strict("ship", "shell", e, &vars));
+    // like `cwd`, relative paths are relative to the file which defines them
+    if let Some(trusted_projects) = ship.trusted_projects.take() {
+        let defining_file = ctx.merged.origin_of("ship.trusted_projects");
+        let base_dir = defining_file.and_then(|f| f.parent());
+        let trusted_projects = trusted_projects
+            .into_iter()
+            .map(|e| {
+                let path = ctx.interpolate_strict("ship", "trusted_projects", e, &vars);
+                expansion::expand_path_with(&path, base_dir, ctx.env_var)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        ship.trusted_projects = Some(trusted_projects);
+    }
+    ship.vars = Some(vars);
+
+    Some(ship)
+}
+
+/// Returns the node at `key_path` in the tree made of `nodes`.
+fn find_node<'n>(nodes: &'n [ConfigNode], key_path: &str) -> Option<&'n ConfigNode> {
+    nodes.iter().find_map(|node| {
+        if node.key_path == key_path {
+            Some(node)
+        } else {
+            find_node(node.children.as_deref().unwrap_or_default(), key_path)
+        }
+    })
+}
+
+fn find_node_mut<'n>(nodes: &'n mut [ConfigNode], key_path: &str) -> Option<&'n mut ConfigNode> {
+    nodes.iter_mut().find_map(|node| {
+        if node.key_path == key_path {
+            Some(node)
+        } else {
+            find_node_mut(node.children.as_deref_mut().unwrap_or_default(), key_path)
+        }
     })
 }
 
-pub fn read_config() -> AppConfig {
-    let toml_dir = config_aggregator::get_proper_config_directory();
-    let content = config_aggregator::get_aggregated_tomls(toml_dir);
+/// Sorts every level of the tree made of `nodes`: pinned nodes come first, then the nodes
+/// with an explicit `order`, and then the rest in `node_order`. As the sort is stable, nodes
+/// which compare equal keep the order in which they were defined.
+fn sort_nodes(nodes: &mut [ConfigNode], node_order: NodeOrder) {
+    nodes.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then_with(|| a.order.is_none().cmp(&b.order.is_none()))
+            .then_with(|| a.order.cmp(&b.order))
+            .then_with(|| match node_order {
+                NodeOrder::Alphabetical => a.name.cmp(&b.name),
+                NodeOrder::File => Ordering::Equal,
+            })
+    });
+
+    for node in nodes {
+        if let Some(children) = &mut node.children {
+            sort_nodes(children, node_order);
+        }
+    }
+}
+
+/// Removes the unavailable nodes from the tree made of `nodes`, together with the groups
+/// which end up empty because of that.
+fn hide_unavailable_nodes(nodes: &mut Vec<ConfigNode>) {
+    nodes.retain(|e| e.unavailable.is_none());
+
+    for node in nodes.iter_mut() {
+        if let Some(children) = &mut node.children {
+            hide_unavailable_nodes(children);
+        }
+    }
+
+    nodes.retain(|e| e.generator.is_some() || e.children.as_ref().is_none_or(|c| !c.is_empty()));
+}
+
+/// Turns the alias at `alias_idx` of `aliases` into a copy of the node it refers to, which is
+/// looked up in `nodes` first and then in `fallback`. Aliases of aliases are resolved first:
+/// `stack` holds the aliases being resolved, and `outcomes` the ones which have already been
+/// resolved (or failed). Returns whether the alias could be resolved.
+fn resolve_alias(
+    alias_idx: usize,
+    aliases: &[AliasRef],
+    nodes: &mut [ConfigNode],
+    fallback: &[ConfigNode],
+    stack: &mut Vec<usize>,
+    outcomes: &mut HashMap<usize, bool>,
+    ctx: &mut ParseContext,
+) -> bool {
+    let alias = &aliases[alias_idx];
+
+    if let Some(&resolved) = outcomes.get(&alias_idx) {
+        return resolved;
+    }
+
+    // the alias is already being resolved further up the stack, so the references loop
+    if let Some(start) = stack.iter().position(|&e| e == alias_idx) {
+        let cycle = stack[start..]
+            .iter()
+            .chain(std::iter::once(&alias_idx))
+            .map(|&idx| aliases[idx].key_path.clone())
+            .collect();
+
+        ctx.error(
+            &alias.table_path,
+            "ref",
+            ConfigErrorKind::ReferenceCycle(cycle),
+        );
+        return false;
+    }
 
-    let value = content.parse::<Value>().unwrap();
-    let root_table = value.as_table().unwrap();
+    let target_path = join_key_path(ctx.key_prefix, &alias.target);
 
-    let to_skip = vec!["ship"];
+    stack.push(alias_idx);
+    let target_is_resolved = match aliases.iter().position(|e| e.key_path == target_path) {
+        Some(target_idx) => {
+            resolve_alias(target_idx, aliases, nodes, fallback, stack, outcomes, ctx)
+        }
+        None => true,
+    };
+    stack.pop();
+
+    let target = find_node(nodes, &target_path)
+        .or_else(|| find_node(fallback, &alias.target))
+        .cloned();
+
+    let resolved = match target {
+        _ if !target_is_resolved => false,
+        None => {
+            ctx.error(
+                &alias.table_path,
+                "ref",
+                ConfigErrorKind::DanglingReference(alias.target.clone()),
+            );
+            false
+        }
+        Some(target) if !target.is_leaf() => {
+            ctx.error(
+                &alias.table_path,
+                "ref",
+                ConfigErrorKind::ReferenceToGroup(alias.target.clone()),
+            );
+            false
+        }
+        Some(mut target) => {
+            let alias_node = find_node_mut(nodes, &alias.key_path).unwrap();
+
+            // where the alias is shown is up to the alias itself
+            target.key_path = alias.key_path.clone();
+            target.order = alias_node.order;
+            target.pinned = alias_node.pinned;
+            target.unavailable = alias_node.unavailable.take().or(target.unavailable);
+            if alias.has_name {
+                target.name = alias_node.name.clone();
+            }
+            if alias.has_description {
+                target.description = alias_node.description.clone();
+            }
+
+            *alias_node = target;
+            true
+        }
+    };
+
+    outcomes.insert(alias_idx, resolved);
+    resolved
+}
+
+/// Parses all the top level tables (except the `ship` table) of `root_table` into a sorted
+/// list of [ConfigNode]s, which can use the variables in `vars`. Aliases (see
+/// [NodeSchema::reference]) can refer to the parsed nodes, or else to the ones in `fallback`.
+fn parse_top_level_nodes(
+    root_table: &Map<String, Value>,
+    vars: &BTreeMap<String, String>,
+    fallback: &[ConfigNode],
+    ctx: &mut ParseContext,
+) -> Vec<ConfigNode> {
+    let defaults = NodeDefaults {
+        vars: vars.clone(),
+        ..Default::default()
+    };
+
+    let to_skip = ["ship"];
 
     let mut top_level_children: Vec<ConfigNode> = root_table
-        .keys()
-        .into_iter()
-        .filter(|&e| !to_skip.contains(&e.as_str()))
-        .filter_map(|e| root_table.get(e))
-        .map(parse_nodes)
+        .iter()
+        .filter(|&(k, _)| !to_skip.contains(&k.as_str()))
+        .filter_map(|(k, v)| parse_nodes(v, "", k, &defaults, ctx))
         .collect();
 
-    top_level_children.sort_by_key(|e| e.name.to_string());
+    let aliases = std::mem::take(&mut ctx.aliases);
+    let mut outcomes = HashMap::new();
+    for idx in 0..aliases.len() {
+        resolve_alias(
+            idx,
+            &aliases,
+            &mut top_level_children,
+            fallback,
+            &mut vec![],
+            &mut outcomes,
+            ctx,
+        );
+    }
+
+    if ctx.unavailable_nodes == UnavailableNodes::Hide {
+        hide_unavailable_nodes(&mut top_level_children);
+    }
+
+    // sorting after resolving the aliases, as they might have taken the name of the node
+    // they refer to
+    sort_nodes(&mut top_level_children, ctx.node_order);
+
+    top_level_children
+}
+
+/// Parses the configuration of a project (see [config_aggregator::get_project_configs])
+/// into a group node which contains all the nodes defined by the project. The project's
+/// `ship` table, if any, is returned alongside it, and is layered on top of `base` (the
+/// config of the global tree and of the outer projects).
+///
+/// Unless `base` trusts the project (see [EddieConfig::trusts]), its nodes are marked as
+/// untrusted and the keys of its `ship` table which change how every command runs are
+/// ignored.
+fn parse_project_config(
+    merged: &MergedConfig,
+    project: &config_aggregator::ProjectConfig,
+    base: &EddieConfig,
+    global_nodes: &[ConfigNode],
+    diagnostics: &mut Diagnostics,
+) -> (ConfigNode, Option<ShipSchema>) {
+    let root_table = merged.value.as_table().unwrap();
+    let project_name = match project.root.file_name() {
+        Some(n) => n.to_string_lossy().to_string(),
+        None => project.root.to_string_lossy().to_string(),
+    };
+    let project_key = format!("project:{}", project_name);
+
+    let mut ctx = ParseContext::new(merged, diagnostics, &project_key);
+
+    let trusted = base.trusts(&project.root);
+    let mut ship = get_ship_config(root_table, &base.vars, &mut ctx);
+    if let Some(ship) = ship.as_mut().filter(|_| !trusted) {
+        let ignored = [
+            ("terminal_emulator", ship.terminal_emulator.take().is_some()),
+            (
+                "terminal_emulator_command_arg",
+                ship.terminal_emulator_command_arg.take().is_some(),
+            ),
+            ("shell", ship.shell.take().is_some()),
+            ("trusted_projects", ship.trusted_projects.take().is_some()),
+        ];
+        for (key, _) in ignored.iter().filter(|(_, is_set)| *is_set) {
+            let kind = ConfigErrorKind::UntrustedProjectKey(key.to_string());
+            ctx.warning("ship", key, kind);
+        }
+    }
+
+    let vars = match ship.as_ref().and_then(|e| e.vars.as_ref()) {
+        Some(vars) => vars,
+        None => &base.vars,
+    };
+    ctx.node_order = ship
+        .as_ref()
+        .and_then(|e| e.node_order)
+        .unwrap_or(base.node_order);
+    ctx.unavailable_nodes = ship
+        .as_ref()
+        .and_then(|e| e.unavailable_nodes)
+        .unwrap_or(base.unavailable_nodes);
 
-    let eddie_config = match get_eddie_config(root_table) {
-        Some(c) => c,
-        None => EddieConfig {
-            ship_name: String::from("Heart of Gold"),
-            terminal_emulator: String::from("alacritty"),
-            terminal_emulator_command_arg: String::from("--command"),
-            shell: String::from("bash"),
+    let mut children = parse_top_level_nodes(root_table, vars, global_nodes, &mut ctx);
+    if !trusted {
+        mark_untrusted(&mut children);
+    }
+
+    let node = ConfigNode {
+        key_path: project_key.clone(),
+        name: format!("Project: {}", project_name),
+        description: format!(
+            "Commands defined by the project at {}",
+            project.root.display()
+        ),
+        children: if !children.is_empty() {
+            Some(children)
+        } else {
+            None
         },
+        untrusted: !trusted,
+        ..Default::default()
     };
 
-    AppConfig {
-        eddie_config: eddie_config,
-        config_tree: ConfigNode {
+    (node, ship)
+}
+
+/// Marks every node of the tree made of `nodes` as untrusted.
+fn mark_untrusted(nodes: &mut [ConfigNode]) {
+    for node in nodes {
+        node.untrusted = true;
+        mark_untrusted(node.children.as_deref_mut().unwrap_or_default());
+    }
+}
+
+/// Reads the global config and every project config (see
+/// [config_aggregator::get_project_configs]) into an [AppConfig]. If any problem is found
+/// in the config then all of them are returned instead.
+///
+/// The global config is read from `config_path` (a directory or a single file) if it is
+/// set, otherwise it is searched for (see [config_aggregator::config_search_order]).
+pub fn read_config(config_path: Option<&Path>) -> Result<AppConfig, ConfigErrors> {
+    let toml_dir = match config_aggregator::get_proper_config_directory(config_path) {
+        Ok(dir) => dir,
+        Err(searched) => {
+            return Err(ConfigErrors(vec![ConfigError {
+                file: None,
+                key_path: String::new(),
+                location: None,
+                kind: ConfigErrorKind::ConfigNotFound(
+                    searched.iter().map(|e| e.to_string()).collect(),
+                ),
+            }]))
+        }
+    };
+    let toml_files = config_aggregator::get_config_files(toml_dir.clone());
+
+    let mut merged = config_merger::merge_files(&toml_files);
+    // new files in the config directory are picked up too, and included files can be
+    // outside of it
+    let mut watched_paths = vec![toml_dir.clone()];
+    watched_paths.extend(merged.files().cloned());
+    let root_table = merged.value.as_table().unwrap();
+
+    let mut diagnostics = Diagnostics::default();
+    let mut ctx = ParseContext::new(&merged, &mut diagnostics, "");
+
+    // fields which are not set in the `ship` table keep their default value
+    let mut eddie_config = EddieConfig::default();
+    if let Some(ship) = get_ship_config(root_table, &BTreeMap::new(), &mut ctx) {
+        ship.apply_to(&mut eddie_config);
+    }
+    ctx.node_order = eddie_config.node_order;
+    ctx.unavailable_nodes = eddie_config.unavailable_nodes;
+
+    let mut top_level_children =
+        parse_top_level_nodes(root_table, &eddie_config.vars, &[], &mut ctx);
+
+    // project configs are layered on top of the global one. They're shown first, and the
+    // `ship` tables of the projects override the global one field by field (the innermost
+    // project taking precedence over all the others).
+    let cwd = std::env::current_dir().ok();
+    let projects = match &cwd {
+        Some(cwd) => config_aggregator::get_project_configs(cwd, &toml_dir),
+        None => vec![],
+    };
+
+    let mut project_nodes = vec![];
+    for project in projects.iter().rev() {
+        let mut project_merged = config_merger::merge_files(&project.files);
+        watched_paths.push(project.root.join(".eddie"));
+        watched_paths.extend(project.files.iter().cloned());
+        watched_paths.extend(project_merged.files().cloned());
+
+        let (project_node, project_ship) = parse_project_config(
+            &project_merged,
+            project,
+            &eddie_config,
+            &top_level_children,
+            &mut diagnostics,
+        );
+
+        merged.conflicts.append(&mut project_merged.conflicts);
+        merged.warnings.append(&mut project_merged.warnings);
+        merged.errors.append(&mut project_merged.errors);
+
+        if let Some(ship) = project_ship {
+            ship.apply_to(&mut eddie_config);
+        }
+
+        // projects which only override the `ship` table don't get a group
+        if project_node.children.is_some() {
+            project_nodes.insert(0, project_node);
+        }
+    }
+
+    // the groups generated from the task files of the working directory come right after
+    // the projects
+    let mut provided = match &cwd {
+        Some(cwd) => providers::provided_groups(cwd, &eddie_config.providers, &eddie_config.shell),
+        None => ProvidedGroups::default(),
+    };
+    for group in &mut provided.nodes {
+        if let Some(children) = &mut group.children {
+            sort_nodes(children, eddie_config.node_order);
+        }
+    }
+    watched_paths.append(&mut provided.files);
+    merged.warnings.append(&mut provided.warnings);
+
+    project_nodes.append(&mut provided.nodes);
+    project_nodes.append(&mut top_level_children);
+    let top_level_children = project_nodes;
+
+    // files which can't be parsed are reported first, their nodes are missing from the tree
+    let mut errors = std::mem::take(&mut merged.errors);
+    errors.append(&mut diagnostics.errors);
+    if !errors.is_empty() {
+        return Err(ConfigErrors(errors));
+    }
+
+    Ok(AppConfig {
+        eddie_config,
+        config_tree: ConfigTree::new(ConfigNode {
+            key_path: "".to_string(),
             name: "Root config node".to_string(),
             description: "This is the root node of the configuration tree".to_string(),
-            command: "".to_string(),
-            opens_external: false,
             children: if !top_level_children.is_empty() {
                 Some(top_level_children)
             } else {
                 None
             },
-        },
+            ..Default::default()
+        }),
+        merge_conflicts: merged.conflicts,
+        merge_warnings: merged.warnings,
+        config_warnings: diagnostics.warnings,
+        watched_paths,
+        config_location: toml_dir,
+        config_override: config_path.map(|e| e.to_path_buf()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// The environment variables seen by the parser in the tests.
+    fn test_env_var(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/arthur".to_string()),
+            "EDDIE_TEST_TARGET" => Some("release".to_string()),
+            "EDDIE_TEST_USER" => Some("arthur".to_string()),
+            _ => None,
+        }
+    }
+
+    fn parse_sources(files: &[(&str, &str)]) -> (Vec<ConfigNode>, Diagnostics) {
+        let sources: Vec<_> = files
+            .iter()
+            .map(|(f, c)| (PathBuf::from(f), c.to_string()))
+            .collect();
+        let merged = config_merger::merge_sources(&sources);
+
+        let mut diagnostics = Diagnostics::default();
+        let mut ctx = ParseContext::new(&merged, &mut diagnostics, "");
+        ctx.env_var = test_env_var;
+        let nodes = parse_top_level_nodes(
+            merged.value.as_table().unwrap(),
+            &BTreeMap::new(),
+            &[],
+            &mut ctx,
+        );
+
+        (nodes, diagnostics)
+    }
+
+    #[test]
+    fn test_parse_valid_config() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "work.toml",
+            "[work]\nname = \"Work\"\n[work.hi]\nname = \"Hi\"\ncommand = \"echo hi\"\nexternal = true",
+        )]);
+
+        assert_eq!(diagnostics.errors, vec![]);
+        assert_eq!(diagnostics.warnings, vec![]);
+        assert_eq!(nodes.len(), 1);
+
+        let hi = &nodes[0].children.as_ref().unwrap()[0];
+        assert_eq!(hi.key_path, "work.hi");
+        assert_eq!(hi.name, "Hi");
+        assert_eq!(hi.command, "echo hi");
+        assert!(hi.opens_external);
+    }
+
+    #[test]
+    fn test_all_errors_are_reported_with_their_location() {
+        let (_, diagnostics) = parse_sources(&[
+            (
+                "work.toml",
+                "[work]\nname = \"Work\"\n\n[work.hi]\nname = \"Hi\"\nexternal = \"yes\"",
+            ),
+            ("other.toml", "[other]\ndescription = \"no name\"\n"),
+            ("top.toml", "not_a_table = 1\n"),
+        ]);
+
+        assert_eq!(
+            diagnostics.errors,
+            vec![
+                ConfigError {
+                    file: Some(PathBuf::from("work.toml")),
+                    key_path: "work.hi.external".to_string(),
+                    location: Some((6, 1)),
+                    kind: ConfigErrorKind::InvalidValue {
+                        key: "external".to_string(),
+                        message:
+                            "invalid type: string \"yes\", expected a boolean for key `external`"
+                                .to_string(),
+                    },
+                },
+                ConfigError {
+                    file: Some(PathBuf::from("other.toml")),
+                    key_path: "other.name".to_string(),
+                    location: Some((1, 1)),
+                    kind: ConfigErrorKind::MissingKey("name".to_string()),
+                },
+                ConfigError {
+                    file: Some(PathBuf::from("top.toml")),
+                    key_path: "not_a_table".to_string(),
+                    location: Some((1, 1)),
+                    kind: ConfigErrorKind::NotATable { found: "integer" },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_formats_can_be_mixed() {
+        let (nodes, diagnostics) = parse_sources(&[
+            ("a.toml", "[work]\nname = \"Work\"\n"),
+            (
+                "b.yaml",
+                "work:\n  hi:\n    name: Hi\n    command: echo hi\n    external: yes\n",
+            ),
+            (
+                "c.json",
+                "{\n  \"work\": {\n    \"ls\": { \"command\": \"ls\" }\n  }\n}",
+            ),
+        ]);
+
+        assert_eq!(nodes[0].children.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            diagnostics
+                .errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "b.yaml:5:5: `work.hi.external`: invalid type: string \"yes\", expected a \
+                 boolean for key `external`",
+                "c.json:3:5: `work.ls.name`: this required key is missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_are_warnings() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "work.toml",
+            "[work]\nname = \"Work\"\n[work.hi]\nname = \"Hi\"\ncomand = \"echo hi\"",
+        )]);
+
+        assert_eq!(diagnostics.errors, vec![]);
+        assert_eq!(
+            diagnostics.warnings,
+            vec![ConfigError {
+                file: Some(PathBuf::from("work.toml")),
+                key_path: "work.hi.comand".to_string(),
+                location: Some((5, 1)),
+                kind: ConfigErrorKind::UnknownKey("comand".to_string()),
+            }]
+        );
+        assert_eq!(nodes[0].children.as_ref().unwrap()[0].command, "");
+    }
+
+    #[test]
+    fn test_cwd_is_relative_to_the_defining_file() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "/configs/work.toml",
+            "[build]\nname = \"Build\"\ncommand = \"make\"\ncwd = \"../src\"\n[build.env]\nTARGET = \"$EDDIE_TEST_TARGET\"",
+        )]);
+
+        assert_eq!(diagnostics.errors, vec![]);
+        assert_eq!(nodes[0].cwd, Some(PathBuf::from("/configs/../src")));
+        assert_eq!(nodes[0].env["TARGET"], "release");
+        assert!(nodes[0].is_leaf());
+    }
+
+    #[test]
+    fn test_group_settings_are_inherited() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "/configs/work.toml",
+            r#"
+[k8s]
+name = "K8s"
+shell = "zsh"
+cwd = "/srv"
+command_prefix = "kubectl"
+env = { KUBECONFIG = "/k8s/config", NS = "default" }
+
+[k8s.pods]
+name = "Pods"
+command = "get pods"
+
+[k8s.logs]
+name = "Logs"
+command = "logs -f app"
+shell = "fish"
+external = true
+env = { NS = "prod" }
+"#,
+        )]);
+
+        assert_eq!(diagnostics.errors, vec![]);
+
+        let children = nodes[0].children.as_ref().unwrap();
+        let (logs, pods) = (&children[0], &children[1]);
+
+        assert_eq!(pods.command, "kubectl get pods");
+        assert_eq!(pods.shell, Some("zsh".to_string()));
+        assert_eq!(pods.cwd, Some(PathBuf::from("/srv")));
+        assert_eq!(pods.env["NS"], "default");
+        assert!(!pods.opens_external);
+
+        assert_eq!(logs.command, "kubectl logs -f app");
+        assert_eq!(logs.shell, Some("fish".to_string()));
+        assert_eq!(logs.env["KUBECONFIG"], "/k8s/config");
+        assert_eq!(logs.env["NS"], "prod");
+        assert!(logs.opens_external);
+    }
+
+    #[test]
+    fn test_scripts() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "work.toml",
+            r#"
+[py]
+name = "Python"
+interpreter = "python3"
+
+[py.hi]
+name = "Hi"
+script = """
+import sys
+print("hi", sys.argv[1])
+"""
+
+[py.both]
+name = "Both"
+command = "echo hi"
+script = "print('hi')"
+"#,
+        )]);
+
+        let children = nodes[0].children.as_ref().unwrap();
+        let hi = &children[1];
+
+        assert!(hi.is_command());
+        assert_eq!(hi.interpreter, Some("python3".to_string()));
+        assert_eq!(
+            hi.script,
+            Some("import sys\nprint(\"hi\", sys.argv[1])\n".to_string())
+        );
+        assert_eq!(
+            diagnostics.errors,
+            vec![ConfigError {
+                file: Some(PathBuf::from("work.toml")),
+                key_path: "py.both.script".to_string(),
+                location: Some((16, 1)),
+                kind: ConfigErrorKind::ConflictingKeys {
+                    key: "script".to_string(),
+                    other: "command".to_string(),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_steps() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "work.toml",
+            "[ci]\nname = \"CI\"\ncommand_prefix = \"cargo\"\nsteps = [\"fmt --check\", \"test {{filter}}\"]\ncommand = \"make\"",
+        )]);
+
+        assert_eq!(
+            nodes[0].steps,
+            vec!["cargo fmt --check", "cargo test {{filter}}"]
+        );
+        assert_eq!(
+            diagnostics
+                .errors
+                .iter()
+                .map(|e| (e.key_path.as_str(), &e.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "ci.steps",
+                    &ConfigErrorKind::ConflictingKeys {
+                        key: "steps".to_string(),
+                        other: "command".to_string(),
+                    }
+                ),
+                (
+                    "ci.steps",
+                    &ConfigErrorKind::UndeclaredArgument("filter".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "work.toml",
+            r#"
+[system]
+name = "System"
+cwd = "/etc"
+
+[system.vpn]
+name = "Restart VPN"
+description = "Restarts the VPN"
+command = "systemctl restart vpn"
+
+[work]
+name = "Work"
+
+[work.vpn]
+ref = "system.vpn"
+
+[work.vpn2]
+ref = "work.vpn"
+name = "VPN again"
+
+[work.vpn3]
+ref = "system.vpn"
+order = 1
+
+[work.vpn4]
+ref = "system.vpn"
+when = { on_path = "surely-not-a-real-program" }
+"#,
+        )]);
+
+        assert_eq!(diagnostics.errors, vec![]);
+
+        // the alias with an `order` comes first, and the one whose `when` isn't met is hidden
+        let work = nodes[1].children.as_ref().unwrap();
+        assert_eq!(work.len(), 3);
+        assert_eq!(work[0].key_path, "work.vpn3");
+        assert_eq!(work[0].order, Some(1));
+        assert_eq!(work[0].command, "systemctl restart vpn");
+        assert_eq!(work[1].key_path, "work.vpn");
+        assert_eq!(work[1].name, "Restart VPN");
+        assert_eq!(work[1].description, "Restarts the VPN");
+        assert_eq!(work[1].command, "systemctl restart vpn");
+        assert_eq!(work[1].cwd, Some(PathBuf::from("/etc")));
+        assert_eq!(work[2].key_path, "work.vpn2");
+        assert_eq!(work[2].name, "VPN again");
+        assert_eq!(work[2].command, "systemctl restart vpn");
+        assert!(nodes[0].children.as_ref().unwrap()[0].unavailable.is_none());
+    }
+
+    #[test]
+    fn test_invalid_aliases() {
+        let (_, diagnostics) = parse_sources(&[(
+            "work.toml",
+            r#"
+[a]
+ref = "b"
+[b]
+ref = "a"
+[c]
+ref = "nope"
+[d]
+name = "D"
+[d.e]
+name = "E"
+command = "ls"
+[f]
+ref = "d"
+command = "ls"
+[g]
+ref = "d.e"
+cwd = "/tmp"
+env = { A = "1" }
+confirm = true
+when = { os = "linux" }
+[g.h]
+name = "H"
+command = "ls"
+"#,
+        )]);
+
+        assert_eq!(
+            diagnostics
+                .errors
+                .iter()
+                .map(|e| (e.key_path.as_str(), e.location, e.kind.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "f.command",
+                    Some((15, 1)),
+                    "`command` can't be used together with `ref`".to_string()
+                ),
+                (
+                    "g.cwd",
+                    Some((18, 1)),
+                    "`cwd` can't be set on an alias, which only takes `name`, `description`, \
+                     `order`, `pinned` and `when`"
+                        .to_string()
+                ),
+                (
+                    "g.env",
+                    Some((19, 1)),
+                    "`env` can't be set on an alias, which only takes `name`, `description`, \
+                     `order`, `pinned` and `when`"
+                        .to_string()
+                ),
+                (
+                    "g.confirm",
+                    Some((20, 1)),
+                    "`confirm` can't be set on an alias, which only takes `name`, \
+                     `description`, `order`, `pinned` and `when`"
+                        .to_string()
+                ),
+                (
+                    "g.h",
+                    Some((22, 1)),
+                    "`h` can't be set on an alias, which only takes `name`, `description`, \
+                     `order`, `pinned` and `when`"
+                        .to_string()
+                ),
+                (
+                    "a.ref",
+                    Some((3, 1)),
+                    "the references form a cycle: a -> b -> a".to_string()
+                ),
+                (
+                    "c.ref",
+                    Some((7, 1)),
+                    "there is no node at `nope`".to_string()
+                ),
+                (
+                    "f.ref",
+                    Some((14, 1)),
+                    "`d` is a group, only commands can be referenced".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vars() {
+        let merged = config_merger::merge_sources(&[(
+            PathBuf::from("main.toml"),
+            r#"
+[ship]
+shell = "${shell_dir}/zsh"
+vars = { shell_dir = "/opt/bin", host = "${EDDIE_TEST_USER}@example.com" }
+
+[ssh]
+name = "SSH"
+vars = { port = "2222" }
+
+[ssh.connect]
+name = "Connect"
+description = "Connect to ${host}"
+command = "ssh -p ${port} ${host} ${REGION:-eu-west-1} $${HOME}"
+preview_command = "ssh-keygen -F ${host}"
+cwd = "${HOME}"
+
+[ssh.checkout]
+name = "Checkout"
+script = "git checkout ${1:-main} && echo ${host} ${EDDIE_TEST_USER:-x}"
+
+[ssh.broken]
+name = "Broken"
+description = "Runs in ${srcdir}"
+command = "for i in 1 2; do echo ${i}; done"
+cwd = "${srcdir}/app"
+"#
+            .to_string(),
+        )]);
+        let root_table = merged.value.as_table().unwrap();
+
+        let mut diagnostics = Diagnostics::default();
+        let mut ctx = ParseContext::new(&merged, &mut diagnostics, "");
+        ctx.env_var = test_env_var;
+        let ship = get_ship_config(root_table, &BTreeMap::new(), &mut ctx).unwrap();
+        let mut config = EddieConfig::default();
+        ship.apply_to(&mut config);
+        let nodes = parse_top_level_nodes(root_table, &config.vars, &[], &mut ctx);
+
+        assert_eq!(config.shell, "/opt/bin/zsh");
+
+        let connect = &nodes[0].children.as_ref().unwrap()[2];
+        assert_eq!(connect.description, "Connect to arthur@example.com");
+        assert_eq!(
+            connect.preview_command.as_deref(),
+            Some("ssh-keygen -F arthur@example.com")
+        );
+        assert_eq!(
+            connect.command,
+            "ssh -p 2222 arthur@example.com eu-west-1 ${HOME}"
+        );
+        assert_eq!(connect.cwd, Some(PathBuf::from("/home/arthur")));
+
+        // scripts are left to their interpreter, including the defaults of their arguments
+        let script = &nodes[0].children.as_ref().unwrap()[1];
+        assert_eq!(
+            script.script.as_deref(),
+            Some("git checkout ${1:-main} && echo ${host} ${EDDIE_TEST_USER:-x}")
+        );
+
+        // unknown variables are left to the shell in commands, and are errors elsewhere
+        let broken = &nodes[0].children.as_ref().unwrap()[0];
+        assert_eq!(broken.command, "for i in 1 2; do echo ${i}; done");
+        assert_eq!(
+            diagnostics
+                .errors
+                .iter()
+                .map(|e| (e.key_path.as_str(), e.location, e.kind.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "ssh.broken.cwd",
+                    Some((25, 1)),
+                    "the variable `srcdir` is not defined (use `${srcdir:-default}` to give it a \
+                     default)"
+                        .to_string()
+                ),
+                (
+                    "ssh.broken.description",
+                    Some((23, 1)),
+                    "the variable `srcdir` is not defined (use `${srcdir:-default}` to give it a \
+                     default)"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_order() {
+        let content = r#"
+[deploy]
+name = "Deploy"
+command = "make deploy"
+
+[build]
+name = "Build"
+command = "make"
+
+[test]
+name = "Test"
+command = "make test"
+order = 2
+
+[lint]
+name = "Lint"
+command = "make lint"
+order = 1
+
+[logs]
+name = "Logs"
+command = "tail -f log"
+pinned = true
+"#;
+        let merged =
+            config_merger::merge_sources(&[(PathBuf::from("main.toml"), content.to_string())]);
+
+        let names_in_order = |node_order| {
+            let mut diagnostics = Diagnostics::default();
+            let mut ctx = ParseContext::new(&merged, &mut diagnostics, "");
+            ctx.node_order = node_order;
+
+            parse_top_level_nodes(
+                merged.value.as_table().unwrap(),
+                &BTreeMap::new(),
+                &[],
+                &mut ctx,
+            )
+            .into_iter()
+            .map(|e| e.name)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names_in_order(NodeOrder::Alphabetical),
+            vec!["Logs", "Lint", "Test", "Build", "Deploy"]
+        );
+        assert_eq!(
+            names_in_order(NodeOrder::File),
+            vec!["Logs", "Lint", "Test", "Deploy", "Build"]
+        );
+    }
+
+    #[test]
+    fn test_confirm() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "main.toml",
+            r#"
+[destroy]
+name = "Destroy"
+command = "terraform destroy"
+confirm = true
+
+[destroy_env]
+name = "Destroy env"
+vars = { environment = "prod" }
+command = "terraform destroy"
+confirm = "${environment}"
+
+[plan]
+name = "Plan"
+command = "terraform plan"
+confirm = false
+
+[apply]
+name = "Apply"
+command = "terraform apply"
+confirm = " "
+"#,
+        )]);
+
+        let find = |key_path| find_node(&nodes, key_path).unwrap();
+        assert_eq!(find("destroy").confirm, Some(Confirmation::Yes));
+        assert_eq!(
+            find("destroy_env").confirm,
+            Some(Confirmation::Word("prod".to_string()))
+        );
+        assert_eq!(find("plan").confirm, None);
+        assert_eq!(find("apply").confirm, None);
+        assert_eq!(diagnostics.errors.len(), 1);
+        assert_eq!(diagnostics.errors[0].key_path, "apply.confirm");
+    }
+
+    #[test]
+    fn test_generators() {
+        let (nodes, diagnostics) = parse_sources(&[(
+            "main.toml",
+            r#"
+[branches]
+name = "Branches"
+command_prefix = "git"
+generator = "git branch --format=%(refname:short)"
+child_command = "checkout {{item}} {{flags}}"
+generator_ttl = 5
+args = [{ name = "flags" }]
+
+[containers]
+name = "Containers"
+generator = "docker ps --format '{{.Names}}'"
+
+[hosts]
+name = "Hosts"
+child_command = "ssh {{item}} {{user}}"
+"#,
+        )]);
+
+        let branches = find_node(&nodes, "branches").unwrap();
+        assert_eq!(branches.children.as_deref().map(|e| e.len()), Some(0));
+        assert_eq!(
+            branches.generator,
+            Some(Generator {
+                command: "git branch --format=%(refname:short)".to_string(),
+                child_command: "git checkout {{item}} {{flags}}".to_string(),
+                ttl: Duration::from_secs(5),
+            })
+        );
+
+        let errors: Vec<_> = diagnostics
+            .errors
+            .iter()
+            .map(|e| e.key_path.as_str())
+            .collect();
+        assert_eq!(errors, vec!["containers.child_command", "hosts.generator"]);
+    }
+
+    #[test]
+    fn test_unavailable_nodes() {
+        let content = r#"
+[docker]
+name = "Docker"
+when = { on_path = "surely-not-a-real-program" }
+
+[docker.ps]
+name = "ps"
+command = "docker ps"
+
+[tools]
+name = "Tools"
+
+[tools.ls]
+name = "ls"
+command = "ls"
+when = { on_path = "sh" }
+
+[tools.plan9]
+name = "Plan 9"
+command = "9 ls"
+when = { os = ["plan9"] }
+"#;
+        let merged =
+            config_merger::merge_sources(&[(PathBuf::from("main.toml"), content.to_string())]);
+
+        let parse = |unavailable_nodes| {
+            let mut diagnostics = Diagnostics::default();
+            let mut ctx = ParseContext::new(&merged, &mut diagnostics, "");
+            ctx.unavailable_nodes = unavailable_nodes;
+
+            let nodes = parse_top_level_nodes(
+                merged.value.as_table().unwrap(),
+                &BTreeMap::new(),
+                &[],
+                &mut ctx,
+            );
+            assert_eq!(diagnostics.errors, vec![]);
+            nodes
+        };
+
+        let hidden = parse(UnavailableNodes::Hide);
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].children.as_ref().unwrap().len(), 1);
+        assert_eq!(hidden[0].children.as_ref().unwrap()[0].name, "ls");
+
+        let greyed_out = parse(UnavailableNodes::GreyOut);
+        assert_eq!(
+            greyed_out[0].children.as_ref().unwrap()[0].unavailable,
+            Some("`surely-not-a-real-program` is not on PATH".to_string())
+        );
+        assert!(greyed_out[1].unavailable.is_none());
+        let tools = greyed_out[1].children.as_ref().unwrap();
+        assert!(tools
+            .iter()
+            .any(|e| e.name == "Plan 9" && e.unavailable.is_some()));
+    }
+
+    #[test]
+    fn test_partial_ship_table() {
+        let merged = config_merger::merge_sources(&[(
+            PathBuf::from("main.toml"),
+            "[ship]\nshell = \"fish\"\nterminal = \"kitty\"".to_string(),
+        )]);
+
+        let mut diagnostics = Diagnostics::default();
+        let ship = get_ship_config(
+            merged.value.as_table().unwrap(),
+            &BTreeMap::new(),
+            &mut ParseContext::new(&merged, &mut diagnostics, ""),
+        );
+
+        let mut config = EddieConfig::default();
+        ship.unwrap().apply_to(&mut config);
+
+        assert_eq!(config.shell, "fish");
+        assert_eq!(config.ship_name, "Heart of Gold");
+        assert_eq!(diagnostics.warnings.len(), 1);
+        assert_eq!(diagnostics.warnings[0].key_path, "ship.terminal");
+    }
+
+    #[test]
+    fn test_untrusted_projects() {
+        let merged = config_merger::merge_sources(&[(
+            PathBuf::from("eddie.toml"),
+            r#"
+[ship]
+name = "Project ship"
+shell = "./evil-shell"
+trusted_projects = ["/"]
+
+[git]
+name = "Git"
+[git.status]
+name = "Status"
+command = "git status"
+preview_command = "git diff --stat"
+"#
+            .to_string(),
+        )]);
+        let project = config_aggregator::ProjectConfig {
+            root: PathBuf::from("/home/arthur/src/app"),
+            files: vec![],
+        };
+
+        let mut diagnostics = Diagnostics::default();
+        let base = EddieConfig::default();
+        let (node, ship) = parse_project_config(&merged, &project, &base, &[], &mut diagnostics);
+
+        let mut config = EddieConfig::default();
+        ship.unwrap().apply_to(&mut config);
+        assert_eq!(config.ship_name, "Project ship");
+        assert_eq!(config.shell, "bash");
+        assert_eq!(config.trusted_projects, Vec::<PathBuf>::new());
+        let ignored: Vec<_> = diagnostics.warnings.iter().map(|e| &e.kind).collect();
+        assert_eq!(
+            ignored,
+            vec![
+                &ConfigErrorKind::UntrustedProjectKey("shell".to_string()),
+                &ConfigErrorKind::UntrustedProjectKey("trusted_projects".to_string()),
+            ]
+        );
+
+        assert!(node.untrusted);
+        let git = &node.children.as_ref().unwrap()[0];
+        assert!(git.untrusted);
+        assert!(git.children.as_ref().unwrap()[0].untrusted);
+
+        // once trusted, the project is parsed like the global config
+        let mut diagnostics = Diagnostics::default();
+        let base = EddieConfig {
+            trusted_projects: vec![project.root.clone()],
+            ..Default::default()
+        };
+        let (node, ship) = parse_project_config(&merged, &project, &base, &[], &mut diagnostics);
+
+        let mut config = EddieConfig::default();
+        ship.unwrap().apply_to(&mut config);
+        assert_eq!(diagnostics.warnings, vec![]);
+        assert_eq!(config.shell, "./evil-shell");
+        assert_eq!(config.trusted_projects, vec![PathBuf::from("/")]);
+        assert!(!node.untrusted);
+        assert!(!node.children.as_ref().unwrap()[0].untrusted);
     }
 }
@@ -0,0 +1,204 @@
+//! The generator module runs the generators of the groups ([Generator]), and turns every line
+//! they print into a child of the group, whose command is the `child_command` of the
+//! generator with `{{item}}` replaced by the line (see [crate::executor::resolve_command]).
+
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::config_reader::config_structs::{ConfigNode, EddieConfig, Generator};
+use crate::executor::{build_command, shell_of, wait_with_timeout};
+
+/// How long a generator can run before Eddie stops waiting for it.
+const GENERATOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the distinct, non empty lines of `output`, in order.
+fn items_of(output: &str) -> Vec<&str> {
+    let mut items: Vec<&str> = vec![];
+
+    for line in output.lines().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        if !items.contains(&line) {
+            items.push(line);
+        }
+    }
+
+    items
+}
+
+/// Builds the child of `group` for `item`. It inherits the settings and the arguments of the
+/// group.
+fn child_node(group: &ConfigNode, generator: &Generator, item: &str) -> ConfigNode {
+    ConfigNode {
+        key_path: format!("{}.{}", group.key_path, item),
+        name: item.to_string(),
+        description: format!("Generated by `{}`", generator.command),
+        command: generator.child_command.clone(),
+        generated_item: Some(item.to_string()),
+        opens_external: group.opens_external,
+        unavailable: group.unavailable.clone(),
+        args: group.args.clone(),
+        confirm: group.confirm.clone(),
+        shell: group.shell.clone(),
+        interpreter: group.interpreter.clone(),
+        cwd: group.cwd.clone(),
+        env: group.env.clone(),
+        untrusted: group.untrusted,
+        ..Default::default()
+    }
+}
+
+/// Runs the generator of `group` with `shell` and returns the children it generates, or why it
+/// failed. The generator is killed if it takes longer than `timeout`.
+fn run_generator(
+    group: &ConfigNode,
+    shell: &str,
+    timeout: Duration,
+) -> Result<Vec<ConfigNode>, String> {
+    let generator = match &group.generator {
+        Some(g) => g,
+        None => return Ok(vec![]),
+    };
+
+    let command_line = [
+        shell.to_string(),
+        "-c".to_string(),
+        generator.command.clone(),
+    ];
+    let mut command = build_command(group, &command_line).unwrap();
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let child = command
+        .spawn()
+        .map_err(|e| format!("failed to run `{}`: {}", generator.command, e))?;
+    let output = match wait_with_timeout(child, timeout) {
+        Some(o) => o,
+        None => {
+            return Err(format!(
+                "`{}` didn't finish within {} seconds",
+                generator.command,
+                timeout.as_secs_f32()
+            ))
+        }
+    };
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{}` failed ({}): {}",
+            generator.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(items_of(&stdout)
+        .into_iter()
+        .map(|item| child_node(group, generator, item))
+        .collect())
+}
+
+/// Runs the generator of `group` and returns the children it generates, or why it failed.
+/// Groups without a generator have no generated children.
+pub fn generate_children(
+    group: &ConfigNode,
+    eddie_config: &EddieConfig,
+) -> Result<Vec<ConfigNode>, String> {
+    run_generator(group, shell_of(group, eddie_config), GENERATOR_TIMEOUT)
+}
+
+/// Starts the generator of `group` in the background (see [generate_children]), so that the
+/// UI isn't blocked while it runs. Its result is sent to the returned receiver once it is done.
+pub fn start_generation(
+    group: &ConfigNode,
+    eddie_config: &EddieConfig,
+) -> Receiver<Result<Vec<ConfigNode>, String>> {
+    let (sender, receiver) = mpsc::channel();
+
+    let group = group.clone();
+    let shell = shell_of(&group, eddie_config).to_string();
+    thread::spawn(move || {
+        let _ = sender.send(run_generator(&group, &shell, GENERATOR_TIMEOUT));
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::executor::resolve_command;
+
+    fn group(generator: &str) -> ConfigNode {
+        ConfigNode {
+            key_path: "git.checkout".to_string(),
+            opens_external: true,
+            generator: Some(Generator {
+                command: generator.to_string(),
+                child_command: "git checkout {{item}} {{flags}}".to_string(),
+                ttl: Duration::from_secs(30),
+            }),
+            children: Some(vec![]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_every_line_becomes_a_child() {
+        let children = generate_children(
+            &group("printf 'main\\n\\nfeature/a b\\nmain\\n'"),
+            &EddieConfig::default(),
+        )
+        .unwrap();
+
+        let names: Vec<_> = children.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "feature/a b"]);
+        assert_eq!(children[1].key_path, "git.checkout.feature/a b");
+        assert_eq!(
+            resolve_command(&children[1], &HashMap::new(), &EddieConfig::default()),
+            "git checkout 'feature/a b' "
+        );
+        assert!(children[1].opens_external);
+        assert!(children[1].is_leaf());
+    }
+
+    #[test]
+    fn test_placeholders_in_items_are_kept() {
+        let children =
+            generate_children(&group("echo 'fix/{{flags}}'"), &EddieConfig::default()).unwrap();
+        let values = [("flags".to_string(), "--force".to_string())].into();
+
+        assert_eq!(
+            resolve_command(&children[0], &values, &EddieConfig::default()),
+            "git checkout 'fix/{{flags}}' '--force'"
+        );
+    }
+
+    #[test]
+    fn test_failing_generators_are_reported() {
+        let error = generate_children(&group("echo nope >&2; exit 3"), &EddieConfig::default())
+            .unwrap_err();
+
+        assert!(error.contains("nope"), "{}", error);
+    }
+
+    #[test]
+    fn test_slow_generators_are_stopped() {
+        let error = run_generator(&group("sleep 5"), "sh", Duration::from_millis(100)).unwrap_err();
+
+        assert_eq!(error, "`sleep 5` didn't finish within 0.1 seconds");
+    }
+
+    #[test]
+    fn test_generation_in_the_background() {
+        let receiver = start_generation(&group("echo main"), &EddieConfig::default());
+
+        let children = receiver.recv().unwrap().unwrap();
+        assert_eq!(children[0].key_path, "git.checkout.main");
+    }
+}
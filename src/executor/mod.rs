@@ -0,0 +1,501 @@
+//! The executor module is in charge of actually running the commands of the config tree,
+//! either inside Eddie's own process or in an external terminal emulator.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config_reader::config_structs::{ConfigNode, EddieConfig};
+use script::ScriptFile;
+
+pub mod generator;
+pub mod preview;
+pub mod script;
+pub mod template;
+
+/// Returns the shell used to run the command of `command_node`.
+fn shell_of<'a>(command_node: &'a ConfigNode, eddie_config: &'a EddieConfig) -> &'a str {
+    command_node.shell.as_deref().unwrap_or(&eddie_config.shell)
+}
+
+/// Returns the interpreter of the script of `command_node`, which defaults to its shell.
+fn interpreter_of<'a>(command_node: &'a ConfigNode, eddie_config: &'a EddieConfig) -> &'a str {
+    command_node
+        .interpreter
+        .as_deref()
+        .unwrap_or_else(|| shell_of(command_node, eddie_config))
+}
+
+/// Returns the values of the arguments of `command_node` in the order in which they're
+/// declared. Scripts receive them as positional arguments.
+fn positional_args(command_node: &ConfigNode, arg_values: &HashMap<String, String>) -> Vec<String> {
+    command_node
+        .args
+        .iter()
+        .map(|arg| arg_values.get(&arg.name).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// Returns the command of `command_node` with all its placeholders replaced by the values
+/// in `arg_values` (see [template::render]). For scripts, this is how the interpreter is
+/// invoked, and steps are chained into a single command.
+pub fn resolve_command(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    eddie_config: &EddieConfig,
+) -> String {
+    let shell = shell_of(command_node, eddie_config);
+
+    if command_node.script.is_some() {
+        let mut command_line = vec![
+            interpreter_of(command_node, eddie_config).to_string(),
+            "<script>".into(),
+        ];
+        command_line.extend(
+            positional_args(command_node, arg_values)
+                .iter()
+                .map(|e| template::quote_for_shell(shell, e)),
+        );
+
+        return command_line.join(" ");
+    }
+
+    if !command_node.steps.is_empty() {
+        let separator = if command_node.continue_on_error {
+            "; "
+        } else {
+            " && "
+        };
+
+        return command_node
+            .steps
+            .iter()
+            .map(|step| template::render(step, arg_values, shell))
+            .collect::<Vec<_>>()
+            .join(separator);
+    }
+
+    // the item of a generated node is rendered together with the arguments, so that the
+    // placeholders it may contain are left as they are
+    match &command_node.generated_item {
+        Some(item) => {
+            let mut values = arg_values.clone();
+            values.insert("item".to_string(), item.clone());
+            template::render(&command_node.command, &values, shell)
+        }
+        None => template::render(&command_node.command, arg_values, shell),
+    }
+}
+
+/// Creates the process described by `command_line` (the program followed by its arguments),
+/// in the working directory and with the environment of `command_node`.
+fn build_command(command_node: &ConfigNode, command_line: &[String]) -> Option<Command> {
+    let (program, args) = command_line.split_first()?;
+
+    let mut command = Command::new(program);
+    command.args(args);
+
+    if let Some(cwd) = &command_node.cwd {
+        command.current_dir(cwd);
+    }
+    command.envs(&command_node.env);
+
+    Some(command)
+}
+
+/// Reads all of `pipe` in its own thread, so that a command printing a lot isn't blocked
+/// while we wait for it to finish.
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        let _ = sender.send(output);
+    });
+
+    receiver
+}
+
+/// Waits for `child` to exit and returns its exit status and what it printed, or kills it and
+/// returns `None` if it takes longer than `timeout`. Used for the commands that Eddie runs on
+/// its own, like previews and generators, so that a stuck one doesn't keep running forever.
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> Option<Output> {
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+
+    // processes started in the background by the command could keep the pipes open
+    let collect = |receiver: Receiver<Vec<u8>>| receiver.recv_timeout(timeout).unwrap_or_default();
+
+    Some(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// Executes the steps of `command_node` one after the other, stopping at the first one that
+/// fails unless `continue_on_error` is set. The output of each step is preceded by a header
+/// with its exit status and how long it took.
+fn run_steps(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    eddie_config: &EddieConfig,
+) -> String {
+    let shell = shell_of(command_node, eddie_config);
+    let total = command_node.steps.len();
+    let mut output = String::new();
+
+    for (idx, step) in command_node.steps.iter().enumerate() {
+        let resolved_step = template::render(step, arg_values, shell);
+        let command_line = [shell.to_string(), "-c".to_string(), resolved_step.clone()];
+        let mut command = build_command(command_node, &command_line).unwrap();
+
+        let started = Instant::now();
+        let result = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .output();
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let succeeded = match result {
+            Ok(o) => {
+                output.push_str(&format!(
+                    "── Step {}/{}: {} ({}, {:.2}s)\n",
+                    idx + 1,
+                    total,
+                    resolved_step,
+                    o.status,
+                    elapsed
+                ));
+                output.push_str(&String::from_utf8_lossy(&o.stdout));
+                o.status.success()
+            }
+            Err(e) => {
+                output.push_str(&format!(
+                    "── Step {}/{}: {} (failed to start: {})\n",
+                    idx + 1,
+                    total,
+                    resolved_step,
+                    e
+                ));
+                false
+            }
+        };
+
+        let skipped = total - idx - 1;
+        if !succeeded && !command_node.continue_on_error && skipped > 0 {
+            output.push_str(&format!(
+                "── Stopped after a failed step, {} step(s) skipped\n",
+                skipped
+            ));
+            break;
+        }
+    }
+
+    output
+}
+
+/// Returns the program (followed by its arguments) that runs the command of `command_node`.
+/// `script` is the file holding its script, if it has one.
+fn command_line(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    script: Option<&Path>,
+    eddie_config: &EddieConfig,
+) -> Vec<String> {
+    match script {
+        Some(script) => {
+            let mut command_line =
+                script::interpreter_command_line(interpreter_of(command_node, eddie_config));
+            command_line.push(script.to_string_lossy().to_string());
+            command_line.extend(positional_args(command_node, arg_values));
+            command_line
+        }
+        None => vec![
+            shell_of(command_node, eddie_config).to_string(),
+            "-c".to_string(),
+            resolve_command(command_node, arg_values, eddie_config),
+        ],
+    }
+}
+
+/// Writes the script of `command_node` to a temporary file, if it has one. The file is
+/// removed once the returned [ScriptFile] is dropped.
+fn write_script(command_node: &ConfigNode) -> Result<Option<ScriptFile>, String> {
+    match command_node.script.as_deref().map(ScriptFile::write) {
+        Some(Ok(s)) => Ok(Some(s)),
+        Some(Err(e)) => Err(format!(
+            "Failed to write the script to a temporary file: {}",
+            e
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Executes the steps of `command_node` one after the other like [run_steps], but attached to
+/// Eddie's own stdin, stdout and stderr. Returns the exit status of the last step which failed
+/// (so of the first one unless `continue_on_error` is set), or of the last step if none did.
+fn run_steps_attached(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    eddie_config: &EddieConfig,
+) -> Result<ExitStatus, String> {
+    let shell = shell_of(command_node, eddie_config);
+    let mut last_status = None;
+    let mut failed_status = None;
+
+    for step in &command_node.steps {
+        let resolved_step = template::render(step, arg_values, shell);
+        let command_line = [shell.to_string(), "-c".to_string(), resolved_step];
+        let status = build_command(command_node, &command_line)
+            .unwrap()
+            .status()
+            .map_err(|e| format!("Failed to execute the command: {}", e))?;
+
+        last_status = Some(status);
+        if !status.success() {
+            failed_status = Some(status);
+            if !command_node.continue_on_error {
+                break;
+            }
+        }
+    }
+
+    failed_status
+        .or(last_status)
+        .ok_or_else(|| "The command has no steps!".to_string())
+}
+
+/// Executes the command of `command_node` attached to Eddie's own stdin, stdout and stderr,
+/// and returns its exit status once it is done. `extra_args` are appended to the command (or
+/// passed to the script after the values of its arguments). Steps are executed one after the
+/// other (see [run_steps_attached]) and can't take `extra_args`, since they would only reach
+/// one of them. External commands run in the current terminal too.
+pub fn run_attached(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    extra_args: &[String],
+    eddie_config: &EddieConfig,
+) -> Result<ExitStatus, String> {
+    if !command_node.steps.is_empty() {
+        if !extra_args.is_empty() {
+            return Err("Extra arguments can't be passed to a command made of steps".to_string());
+        }
+        return run_steps_attached(command_node, arg_values, eddie_config);
+    }
+
+    let script = write_script(command_node)?;
+    let mut command_line = command_line(
+        command_node,
+        arg_values,
+        script.as_ref().map(ScriptFile::path),
+        eddie_config,
+    );
+
+    if script.is_some() {
+        command_line.extend(extra_args.iter().cloned());
+    } else if let Some(resolved) = command_line.last_mut() {
+        let shell = shell_of(command_node, eddie_config);
+        for arg in extra_args {
+            resolved.push(' ');
+            resolved.push_str(&template::quote_for_shell(shell, arg));
+        }
+    }
+
+    let mut command = build_command(command_node, &command_line)
+        .ok_or_else(|| "The interpreter of the script is empty!".to_string())?;
+
+    command
+        .status()
+        .map_err(|e| format!("Failed to execute the command: {}", e))
+}
+
+/// Executes the command of `command_node`, using `arg_values` as the values of its arguments,
+/// and returns its output. `eddie_config` provides the default shell and the terminal emulator.
+pub fn execute_command(
+    command_node: &ConfigNode,
+    arg_values: &HashMap<String, String>,
+    eddie_config: &EddieConfig,
+) -> String {
+    // steps executed in an external terminal are chained into one command instead
+    if !command_node.steps.is_empty() && !command_node.opens_external {
+        return run_steps(command_node, arg_values, eddie_config);
+    }
+
+    // the file is removed once the script is done, or when Eddie exits if it runs in an
+    // external terminal (see below)
+    let script = match write_script(command_node) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    let mut command_line = command_line(
+        command_node,
+        arg_values,
+        script.as_ref().map(ScriptFile::path),
+        eddie_config,
+    );
+
+    if command_node.opens_external {
+        command_line.splice(
+            0..0,
+            vec![
+                eddie_config.terminal_emulator.clone(),
+                eddie_config.terminal_emulator_command_arg.clone(),
+            ],
+        );
+    }
+
+    let mut command = match build_command(command_node, &command_line) {
+        Some(c) => c,
+        None => return "The interpreter of the script is empty!".to_string(),
+    };
+
+    let child = match command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+        Ok(c) => c,
+        // most likely the working directory of the command doesn't exist
+        Err(e) => return format!("Failed to execute the command: {}", e),
+    };
+
+    // if the process opens an external terminal then don't wait for output, but still reap
+    // it in the background once it exits. The terminal may well return before it has read the
+    // script, so the file is kept until Eddie exits.
+    if command_node.opens_external {
+        if let Some(script) = script {
+            script.keep_until_exit();
+        }
+
+        let mut child = child;
+        thread::spawn(move || {
+            let _ = child.wait();
+        });
+
+        String::from("Executing command in external terminal...")
+    } else {
+        match child.wait_with_output() {
+            Ok(o) => String::from_utf8(o.stdout).unwrap(),
+            Err(_) => "There was an error while decoding the command's output!".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_commands_are_killed() {
+        let child = Command::new("sh")
+            .args(["-c", "sleep 5"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let start = Instant::now();
+
+        assert_eq!(wait_with_timeout(child, Duration::from_millis(100)), None);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    fn steps_node(steps: &[&str], continue_on_error: bool) -> ConfigNode {
+        ConfigNode {
+            shell: Some("sh".to_string()),
+            steps: steps.iter().map(|e| e.to_string()).collect(),
+            continue_on_error,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_steps_stop_at_the_first_failure() {
+        let node = steps_node(&["echo one", "exit 3", "echo three"], false);
+
+        let output = run_steps(&node, &HashMap::new(), &EddieConfig::default());
+        let lines: Vec<_> = output.lines().collect();
+
+        assert!(lines[0].starts_with("── Step 1/3: echo one (exit status: 0, "));
+        assert_eq!(lines[1], "one");
+        assert!(lines[2].starts_with("── Step 2/3: exit 3 (exit status: 3, "));
+        assert_eq!(
+            lines[3],
+            "── Stopped after a failed step, 1 step(s) skipped"
+        );
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_steps_can_continue_on_error() {
+        let node = steps_node(&["false", "echo {{who}}"], true);
+        let values = [("who".to_string(), "me".to_string())].into();
+
+        let output = run_steps(&node, &values, &EddieConfig::default());
+
+        assert!(output.contains("── Step 2/2: echo 'me' (exit status: 0, "));
+        assert!(output.ends_with("me\n"));
+        assert_eq!(
+            resolve_command(&node, &values, &EddieConfig::default()),
+            "false; echo 'me'"
+        );
+    }
+
+    #[test]
+    fn test_run_attached_passes_the_exit_code_and_extra_args() {
+        let node = ConfigNode {
+            shell: Some("sh".to_string()),
+            command: "sh -c 'test \"$1\" = \"a b\" && exit 7' sh".to_string(),
+            ..Default::default()
+        };
+        let run = |extra_args: &[&str]| {
+            let extra_args: Vec<_> = extra_args.iter().map(|e| e.to_string()).collect();
+
+            run_attached(&node, &HashMap::new(), &extra_args, &EddieConfig::default())
+                .unwrap()
+                .code()
+        };
+
+        // the extra arguments are quoted and appended to the command
+        assert_eq!(run(&[]), Some(1));
+        assert_eq!(run(&["a b"]), Some(7));
+    }
+
+    #[test]
+    fn test_run_attached_reports_the_failed_steps() {
+        let run = |node: &ConfigNode, extra_args: &[String]| {
+            run_attached(node, &HashMap::new(), extra_args, &EddieConfig::default())
+                .map(|e| e.code())
+        };
+
+        // a failed step is reported even if the next ones succeed
+        let node = steps_node(&["exit 3", "exit 4", "true"], true);
+        assert_eq!(run(&node, &[]), Ok(Some(4)));
+        let node = steps_node(&["true", "exit 3", "exit 4"], false);
+        assert_eq!(run(&node, &[]), Ok(Some(3)));
+        let node = steps_node(&["true", "exit 5"], false);
+        assert_eq!(run(&node, &[]), Ok(Some(5)));
+        let node = steps_node(&["true", "true"], true);
+        assert_eq!(run(&node, &[]), Ok(Some(0)));
+
+        assert_eq!(
+            run(&node, &["--verbose".to_string()]),
+            Err("Extra arguments can't be passed to a command made of steps".to_string())
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! The preview module runs the `preview_command` of the nodes in the background, so that its
+//! output can be shown next to their description while they are selected.
+
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::config_reader::config_structs::{ConfigNode, EddieConfig};
+use crate::executor::{build_command, shell_of, wait_with_timeout};
+
+/// How long a preview can run before it is stopped.
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Waits for `child` to exit and returns what it printed (stderr after stdout), or kills it
+/// if it takes longer than `timeout`.
+fn wait_for_output(child: Child, timeout: Duration) -> String {
+    let output = match wait_with_timeout(child, timeout) {
+        Some(o) => o,
+        None => {
+            return format!(
+                "The preview didn't finish within {} seconds",
+                timeout.as_secs()
+            )
+        }
+    };
+
+    let output = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+
+    output.trim_end().to_string()
+}
+
+/// Starts the `preview_command` of `node` in the background, in its working directory and
+/// with its environment. Its output, or why it couldn't be run, is sent to the returned
+/// receiver once it is done.
+pub fn start_preview(node: &ConfigNode, eddie_config: &EddieConfig) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    let preview_command = node.preview_command.clone().unwrap_or_default();
+    let command_line = [
+        shell_of(node, eddie_config).to_string(),
+        "-c".to_string(),
+        preview_command.clone(),
+    ];
+    let mut command = build_command(node, &command_line).unwrap();
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    thread::spawn(move || {
+        let output = match command.spawn() {
+            Ok(child) => wait_for_output(child, PREVIEW_TIMEOUT),
+            Err(e) => format!("Failed to run `{}`: {}", preview_command, e),
+        };
+        let _ = sender.send(output);
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_output() {
+        let mut node = ConfigNode {
+            preview_command: Some("echo \"$GREETING\"; echo oops >&2".to_string()),
+            ..Default::default()
+        };
+        node.env.insert("GREETING".to_string(), "hi".to_string());
+
+        let receiver = start_preview(&node, &EddieConfig::default());
+
+        assert_eq!(receiver.recv().unwrap(), "hi\noops");
+    }
+
+    #[test]
+    fn test_slow_previews_are_stopped() {
+        let child = std::process::Command::new("sh")
+            .args(["-c", "sleep 5"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let output = wait_for_output(child, Duration::from_millis(100));
+
+        assert!(
+            output.starts_with("The preview didn't finish"),
+            "{}",
+            output
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! The script module runs the inline `script`s of the config: their body is written to a
+//! temporary file which is then passed to the interpreter of the script.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static SCRIPT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The scripts kept until Eddie exits, see [ScriptFile::keep_until_exit].
+static KEPT_SCRIPTS: Mutex<Vec<ScriptFile>> = Mutex::new(Vec::new());
+
+/// How many names are tried before giving up, if the files already exist.
+const MAX_ATTEMPTS: usize = 16;
+
+/// Returns a name for a script file which is unlikely to be used already.
+fn unique_name() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|e| e.subsec_nanos())
+        .unwrap_or_default();
+
+    format!(
+        "eddie-script-{}-{}-{}",
+        process::id(),
+        SCRIPT_COUNTER.fetch_add(1, Ordering::Relaxed),
+        nanos
+    )
+}
+
+/// A script written to a temporary file, which is removed when this is dropped.
+#[derive(Debug)]
+pub struct ScriptFile {
+    path: PathBuf,
+}
+
+impl ScriptFile {
+    /// Writes `body` to a new file in the temporary directory of the system, which only the
+    /// current user can read.
+    pub fn write(body: &str) -> io::Result<ScriptFile> {
+        ScriptFile::write_in(&env::temp_dir(), body, unique_name)
+    }
+
+    /// Writes `body` to a new file in `dir`, named by `next_name`. The file must not exist
+    /// yet, otherwise someone else sharing `dir` could have prepared it (or a symlink) to
+    /// run their own script, so another name is tried instead.
+    fn write_in(
+        dir: &Path,
+        body: &str,
+        mut next_name: impl FnMut() -> String,
+    ) -> io::Result<ScriptFile> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        for _ in 0..MAX_ATTEMPTS {
+            let path = dir.join(next_name());
+            let mut file = match options.open(&path) {
+                Ok(f) => f,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+
+            // the file is removed on drop even if writing it fails
+            let script = ScriptFile { path };
+            file.write_all(body.as_bytes())?;
+
+            return Ok(script);
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "couldn't find an unused name for the script file",
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the file until [remove_kept_scripts] is called, when Eddie exits. This is for the
+    /// scripts run in an external terminal: the terminal emulator often returns before the
+    /// script is read, so there is no telling when the file isn't needed anymore.
+    pub fn keep_until_exit(self) {
+        KEPT_SCRIPTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(self);
+    }
+}
+
+/// Removes the files of the scripts kept by [ScriptFile::keep_until_exit].
+pub fn remove_kept_scripts() {
+    KEPT_SCRIPTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Splits `interpreter` (e.g. `python3 -u`) into the program and its arguments.
+pub fn interpreter_command_line(interpreter: &str) -> Vec<String> {
+    interpreter.split_whitespace().map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_file_is_removed_on_drop() {
+        let script = ScriptFile::write("echo hi\n").unwrap();
+        let path = script.path().to_path_buf();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "echo hi\n");
+
+        drop(script);
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_kept_scripts_are_removed_on_exit() {
+        let script = ScriptFile::write("echo hi\n").unwrap();
+        let path = script.path().to_path_buf();
+
+        script.keep_until_exit();
+        assert!(path.exists());
+
+        remove_kept_scripts();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_existing_files_are_not_reused() {
+        let dir = env::temp_dir();
+        let taken = unique_name();
+        fs::write(dir.join(&taken), "echo theirs\n").unwrap();
+
+        let mut names = vec![unique_name(), taken.clone()];
+        let script = ScriptFile::write_in(&dir, "echo ours\n", || names.pop().unwrap()).unwrap();
+
+        assert_ne!(script.path(), dir.join(&taken));
+        assert_eq!(fs::read_to_string(script.path()).unwrap(), "echo ours\n");
+        assert_eq!(
+            fs::read_to_string(dir.join(&taken)).unwrap(),
+            "echo theirs\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(script.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::remove_file(dir.join(&taken)).unwrap();
+    }
+
+    #[test]
+    fn test_interpreter_command_line() {
+        assert_eq!(
+            interpreter_command_line(" python3  -u "),
+            vec!["python3", "-u"]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Commands can contain placeholders such as `{{branch}}`, which are replaced by the values
+//! of the arguments declared by the command (see [crate::config_reader::config_structs::CommandArg]).
+//! Values are quoted for the shell that will run the command, so that whatever the user
+//! types ends up as a single argument.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A placeholder found in a command template.
+#[derive(Debug, PartialEq)]
+struct Placeholder<'a> {
+    /// Byte range of the whole placeholder (braces included) in the template.
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_placeholders(template: &str) -> Vec<Placeholder<'_>> {
+    let mut placeholders = vec![];
+    let mut offset = 0;
+
+    while let Some(open) = template[offset..].find("{{") {
+        let start = offset + open;
+
+        let close = match template[start + 2..].find("}}") {
+            Some(c) => start + 2 + c,
+            None => break,
+        };
+
+        let name = template[start + 2..close].trim();
+        if is_valid_name(name) {
+            placeholders.push(Placeholder {
+                start,
+                end: close + 2,
+                name,
+            });
+            offset = close + 2;
+        } else {
+            // not a placeholder, keep looking right after the opening braces
+            offset = start + 2;
+        }
+    }
+
+    placeholders
+}
+
+/// Returns the names of all the placeholders in `template`, in order of appearance.
+pub fn find_placeholders(template: &str) -> Vec<&str> {
+    parse_placeholders(template)
+        .into_iter()
+        .map(|e| e.name)
+        .collect()
+}
+
+/// Replaces every placeholder in `template` with its value in `values`, quoted for `shell`.
+/// Placeholders without a value, or with an empty one, are removed so that optional
+/// arguments can be left out.
+pub fn render(template: &str, values: &HashMap<String, String>, shell: &str) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut last = 0;
+
+    for placeholder in parse_placeholders(template) {
+        rendered.push_str(&template[last..placeholder.start]);
+
+        match values.get(placeholder.name) {
+            Some(value) if !value.is_empty() => rendered.push_str(&quote_for_shell(shell, value)),
+            _ => {}
+        }
+
+        last = placeholder.end;
+    }
+
+    rendered.push_str(&template[last..]);
+    rendered
+}
+
+/// Quotes `value` so that `shell` treats it as a single literal word.
+pub fn quote_for_shell(shell: &str, value: &str) -> String {
+    let shell_name = Path::new(shell)
+        .file_stem()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match shell_name.as_str() {
+        // inside single quotes fish only understands `\'` and `\\`
+        "fish" => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        // single quoted strings in powershell escape quotes by doubling them
+        "powershell" | "pwsh" => format!("'{}'", value.replace('\'', "''")),
+        "cmd" => format!("\"{}\"", value.replace('"', "\"\"")),
+        // POSIX shells can't escape anything inside single quotes, so close the quotes,
+        // add an escaped quote, and open them again
+        _ => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_placeholders() {
+        assert_eq!(
+            find_placeholders("git checkout {{branch}} && echo {{ msg }} {{not valid}} {{x"),
+            vec!["branch", "msg"]
+        );
+    }
+
+    #[test]
+    fn test_render_quotes_values() {
+        let rendered = render(
+            "git commit -m {{msg}} {{extra}}",
+            &values(&[("msg", "it's done; rm -rf /")]),
+            "bash",
+        );
+
+        assert_eq!(rendered, "git commit -m 'it'\\''s done; rm -rf /' ");
+    }
+
+    #[test]
+    fn test_quote_for_shell() {
+        assert_eq!(quote_for_shell("/usr/bin/fish", "a'b\\c"), "'a\\'b\\\\c'");
+        assert_eq!(quote_for_shell("powershell", "a'b"), "'a''b'");
+        assert_eq!(quote_for_shell("cmd.exe", "a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_for_shell("zsh", "a b"), "'a b'");
+    }
+}
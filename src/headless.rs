@@ -0,0 +1,475 @@
+//! The headless module implements the subcommands which use the config tree without starting
+//! the UI, like `eddie run`.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::ExitStatus;
+
+use serde::Serialize;
+
+use crate::config_reader::config_structs::{AppConfig, ConfigNode, Confirmation};
+use crate::config_reader::config_tree::{ConfigTree, NodeId};
+use crate::executor::{self, generator};
+
+/// Runs the generator of the group `id` and replaces its generated children.
+fn generate(config: &mut AppConfig, id: NodeId) -> Result<(), String> {
+    let children = generator::generate_children(config.config_tree.node(id), &config.eddie_config)?;
+    config.config_tree.set_generated_children(id, children);
+
+    Ok(())
+}
+
+/// Finds the node whose key path is `key_path`. Generated nodes only exist once the generator
+/// of their group has run, so it is run if the closest existing ancestor has one.
+fn find_key_path(config: &mut AppConfig, key_path: &str) -> Result<Option<NodeId>, String> {
+    let tree = &config.config_tree;
+    if let Some(id) = tree.find(key_path) {
+        return Ok(Some(id));
+    }
+
+    let closest_ancestor = key_path
+        .match_indices('.')
+        .rev()
+        .find_map(|(idx, _)| tree.find(&key_path[..idx]));
+
+    match closest_ancestor.filter(|&e| tree.node(e).generator.is_some()) {
+        Some(group) => {
+            generate(config, group)?;
+            Ok(config.config_tree.find(key_path))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Finds the node at the end of `names`, the names of the nodes leading to it (from the top
+/// level) separated by `/`, running the generators of the groups along the way.
+fn find_name_path(config: &mut AppConfig, names: &str) -> Result<NodeId, String> {
+    let mut current = config.config_tree.root();
+
+    for name in names.split('/').map(|e| e.trim()) {
+        if config.config_tree.needs_generation(current) {
+            generate(config, current)?;
+        }
+
+        let tree = &config.config_tree;
+        let parent = if current == tree.root() {
+            "the top level".to_string()
+        } else {
+            format!("\"{}\"", tree.node(current).name)
+        };
+        let children = tree
+            .children(current)
+            .ok_or_else(|| format!("{} is a command, it has no \"{}\"", parent, name))?;
+        let matches: Vec<NodeId> = children
+            .iter()
+            .copied()
+            .filter(|&e| tree.node(e).name == name)
+            .collect();
+
+        current = match matches[..] {
+            [id] => id,
+            [] => return Err(format!("There is no \"{}\" in {}", name, parent)),
+            _ => {
+                return Err(format!(
+                    "There are {} nodes named \"{}\" in {}, use a key path instead",
+                    matches.len(),
+                    name,
+                    parent
+                ))
+            }
+        };
+    }
+
+    Ok(current)
+}
+
+/// Finds the node at `path`, which is either a key path (`work.general.echo_hi`) or the names
+/// of the nodes leading to it separated by `/` (`Work/General/Echo hi`).
+pub fn resolve_node(config: &mut AppConfig, path: &str) -> Result<NodeId, String> {
+    match find_key_path(config, path)? {
+        Some(id) => Ok(id),
+        None => find_name_path(config, path),
+    }
+}
+
+/// Returns the values of the arguments of `node`: the ones given as `name=value` in `pairs`,
+/// and the default value for the others.
+fn arg_values(node: &ConfigNode, pairs: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut values: HashMap<String, String> = node
+        .args
+        .iter()
+        .map(|e| (e.name.clone(), e.default.clone()))
+        .collect();
+
+    for pair in pairs {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("`{}` is not a `name=value` pair", pair))?;
+
+        if !values.contains_key(name) {
+            return Err(format!("The command has no argument named `{}`", name));
+        }
+        values.insert(name.to_string(), value.to_string());
+    }
+
+    for arg in &node.args {
+        let value = &values[&arg.name];
+
+        if arg.required && value.is_empty() {
+            return Err(format!(
+                "The argument `{}` is required, give it with `--arg {}=<value>`",
+                arg.name, arg.name
+            ));
+        }
+        if !arg.choices.is_empty() && !arg.choices.contains(value) {
+            return Err(format!(
+                "The argument `{}` must be one of: {}",
+                arg.name,
+                arg.choices.join(", ")
+            ));
+        }
+    }
+
+    Ok(values)
+}
+
+/// Asks on the terminal to confirm `command`, and returns whether it was.
+fn confirm(confirmation: &Confirmation, command: &str, script: Option<&str>) -> bool {
+    eprintln!(
+        "This command has to be confirmed before it runs:\n  {}",
+        command
+    );
+    // the command line of a script only shows the interpreter
+    if let Some(script) = script {
+        eprintln!("Script:");
+        for line in script.trim_end().lines() {
+            eprintln!("  {}", line);
+        }
+    }
+    match confirmation {
+        Confirmation::Yes => eprint!("Run it? [y/N] "),
+        Confirmation::Word(word) => eprint!("Type \"{}\" to confirm: ", word),
+    }
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    match confirmation {
+        Confirmation::Yes => answer.trim().eq_ignore_ascii_case("y"),
+        Confirmation::Word(word) => answer.trim_end_matches(['\r', '\n']) == word,
+    }
+}
+
+/// Returns the exit code which tells how the command that exited with `status` ended. Like
+/// shells do, `128 + n` means that it was killed by the signal `n`.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Implementation of `eddie run`. Runs the command at `path` (see [resolve_node]) with the
+/// arguments given as `name=value` in `arg_pairs`, and `extra_args` appended to it, and
+/// returns its exit code. Commands with `confirm` are confirmed on the terminal first, unless
+/// `assume_yes` is set.
+pub fn run_command(
+    config: &mut AppConfig,
+    path: &str,
+    arg_pairs: &[String],
+    assume_yes: bool,
+    extra_args: &[String],
+) -> Result<i32, String> {
+    let id = resolve_node(config, path)?;
+    let tree = &config.config_tree;
+    let node = tree.node(id);
+
+    if tree.is_group(id) {
+        return Err(format!("`{}` is a group, not a command", node.key_path));
+    }
+    if let Some(reason) = &node.unavailable {
+        return Err(format!("This command is not available here: {}", reason));
+    }
+
+    let values = arg_values(node, arg_pairs)?;
+
+    if let Some(confirmation) = node.confirm.as_ref().filter(|_| !assume_yes) {
+        let command = executor::resolve_command(node, &values, &config.eddie_config);
+        if !confirm(confirmation, &command, node.script.as_deref()) {
+            return Err("Command cancelled".to_string());
+        }
+    }
+
+    executor::run_attached(node, &values, extra_args, &config.eddie_config).map(exit_code)
+}
+
+/// A node as printed by `eddie list --json`.
+#[derive(Debug, Serialize)]
+struct ListedNode<'t> {
+    key_path: &'t str,
+    name: &'t str,
+    description: &'t str,
+    /// `None` for groups.
+    command: Option<String>,
+    external: bool,
+    /// Why the node is not available, if it is greyed out.
+    unavailable: Option<&'t str>,
+    /// The generator of the group, whose children are not listed.
+    generator: Option<&'t str>,
+    /// `None` for commands.
+    children: Option<Vec<ListedNode<'t>>>,
+}
+
+/// Returns what `node` runs, as written in the config: its command, its steps chained
+/// together, or its script. Groups don't run anything.
+fn command_of(node: &ConfigNode, is_group: bool) -> Option<String> {
+    if is_group {
+        None
+    } else if !node.steps.is_empty() {
+        let separator = if node.continue_on_error { "; " } else { " && " };
+        Some(node.steps.join(separator))
+    } else {
+        Some(node.script.clone().unwrap_or_else(|| node.command.clone()))
+    }
+}
+
+fn listed_node<'t>(tree: &'t ConfigTree, id: NodeId, name: &'t str) -> ListedNode<'t> {
+    let node = tree.node(id);
+
+    ListedNode {
+        key_path: &node.key_path,
+        name,
+        description: &node.description,
+        command: command_of(node, tree.is_group(id)),
+        external: node.opens_external,
+        unavailable: node.unavailable.as_deref(),
+        generator: node.generator.as_ref().map(|e| e.command.as_str()),
+        children: tree.children(id).map(|children| {
+            children
+                .iter()
+                .map(|&e| listed_node(tree, e, &tree.node(e).name))
+                .collect()
+        }),
+    }
+}
+
+/// Writes the descendants of `id` to `output` as the branches of a tree, each line starting
+/// with `indent`.
+fn write_tree(tree: &ConfigTree, id: NodeId, indent: &str, output: &mut String) {
+    let children = tree.children(id).unwrap_or_default();
+
+    for (idx, &child) in children.iter().enumerate() {
+        let is_last = idx == children.len() - 1;
+        let node = tree.node(child);
+
+        output.push_str(indent);
+        output.push_str(if is_last { "└── " } else { "├── " });
+        output.push_str(&format!("{} [{}]", node.name, node.key_path));
+        if let Some(description) = node.description.lines().next().filter(|e| !e.is_empty()) {
+            output.push_str(&format!(" - {}", description));
+        }
+        if let Some(reason) = &node.unavailable {
+            output.push_str(&format!(" (not available: {})", reason));
+        }
+        if let Some(generator) = &node.generator {
+            output.push_str(&format!(" (children generated by `{}`)", generator.command));
+        }
+        output.push('\n');
+
+        let child_indent = format!("{}{}", indent, if is_last { "    " } else { "│   " });
+        write_tree(tree, child, &child_indent, output);
+    }
+}
+
+/// Implementation of `eddie list`. Returns the node at `path` (see [resolve_node]) and all its
+/// descendants, or the whole tree if `path` is `None`, either as an indented tree or as JSON.
+/// The generators of the groups are not run.
+pub fn list_nodes(
+    config: &mut AppConfig,
+    path: Option<&str>,
+    json: bool,
+) -> Result<String, String> {
+    let id = match path {
+        Some(path) => resolve_node(config, path)?,
+        None => config.config_tree.root(),
+    };
+    let tree = &config.config_tree;
+
+    // the root node is named after the ship
+    let name = if id == tree.root() {
+        &config.eddie_config.ship_name
+    } else {
+        &tree.node(id).name
+    };
+
+    if json {
+        return serde_json::to_string_pretty(&listed_node(tree, id, name))
+            .map_err(|e| e.to_string());
+    }
+
+    let mut output = format!("{}\n", name);
+    write_tree(tree, id, "", &mut output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::config_reader::config_structs::{CommandArg, EddieConfig, Generator};
+    use crate::config_reader::config_tree::ConfigTree;
+
+    use super::*;
+
+    fn node(key_path: &str, name: &str, children: Option<Vec<ConfigNode>>) -> ConfigNode {
+        ConfigNode {
+            key_path: key_path.to_string(),
+            name: name.to_string(),
+            command: "true".to_string(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    fn node_with_description(key_path: &str, name: &str, description: &str) -> ConfigNode {
+        ConfigNode {
+            description: description.to_string(),
+            ..node(key_path, name, None)
+        }
+    }
+
+    fn config() -> AppConfig {
+        let mut branches = node("git.branches", "Branches", Some(vec![]));
+        branches.generator = Some(Generator {
+            command: "printf 'main\\ndev\\n'".to_string(),
+            child_command: "git checkout {{item}}".to_string(),
+            ttl: Duration::from_secs(30),
+        });
+
+        let root = node(
+            "",
+            "root",
+            Some(vec![
+                node(
+                    "git",
+                    "Git",
+                    Some(vec![
+                        node_with_description("git.status", "Status", "Shows the status"),
+                        branches,
+                    ]),
+                ),
+                node("a", "Twice", None),
+                node("b", "Twice", None),
+            ]),
+        );
+
+        AppConfig {
+            eddie_config: EddieConfig::default(),
+            config_tree: ConfigTree::new(root),
+            merge_conflicts: vec![],
+            merge_warnings: vec![],
+            config_warnings: vec![],
+            watched_paths: vec![],
+            config_location: PathBuf::new(),
+            config_override: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_node() {
+        let mut config = config();
+        let mut key_path_of = |path: &str| {
+            resolve_node(&mut config, path).map(|e| config.config_tree.node(e).key_path.clone())
+        };
+
+        assert_eq!(key_path_of("git.status"), Ok("git.status".to_string()));
+        assert_eq!(key_path_of("Git / Status"), Ok("git.status".to_string()));
+        // generated nodes can be found both ways
+        assert_eq!(
+            key_path_of("git.branches.dev"),
+            Ok("git.branches.dev".to_string())
+        );
+        assert_eq!(
+            key_path_of("Git/Branches/main"),
+            Ok("git.branches.main".to_string())
+        );
+
+        assert!(key_path_of("Git/Nope").unwrap_err().contains("\"Git\""));
+        assert!(key_path_of("Twice").unwrap_err().contains("2 nodes"));
+        assert!(key_path_of("Git/Status/More").is_err());
+    }
+
+    #[test]
+    fn test_arg_values() {
+        let mut command = node("c", "C", None);
+        command.args = vec![
+            CommandArg {
+                name: "env".to_string(),
+                prompt: "env".to_string(),
+                default: "dev".to_string(),
+                choices: vec!["dev".to_string(), "prod".to_string()],
+                required: true,
+            },
+            CommandArg {
+                name: "msg".to_string(),
+                prompt: "msg".to_string(),
+                default: String::new(),
+                choices: vec![],
+                required: false,
+            },
+        ];
+        let values = |pairs: &[&str]| {
+            let pairs: Vec<_> = pairs.iter().map(|e| e.to_string()).collect();
+            arg_values(&command, &pairs)
+        };
+
+        let got = values(&["msg=a=b"]).unwrap();
+        assert_eq!(got["env"], "dev");
+        assert_eq!(got["msg"], "a=b");
+
+        assert!(values(&["env=staging"]).is_err());
+        assert!(values(&["env="]).is_err());
+        assert!(values(&["other=1"]).is_err());
+        assert!(values(&["msg"]).is_err());
+    }
+
+    #[test]
+    fn test_list_nodes() {
+        let mut config = config();
+        config.eddie_config.ship_name = "Goldar".to_string();
+
+        assert_eq!(
+            list_nodes(&mut config, None, false).unwrap(),
+            "Goldar
+├── Git [git]
+│   ├── Status [git.status] - Shows the status
+│   └── Branches [git.branches] (children generated by `printf 'main\\ndev\\n'`)
+├── Twice [a]
+└── Twice [b]
+"
+        );
+        assert_eq!(
+            list_nodes(&mut config, Some("Git/Status"), false).unwrap(),
+            "Status\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&list_nodes(&mut config, Some("git"), true).unwrap()).unwrap();
+        assert_eq!(json["key_path"], "git");
+        assert_eq!(json["command"], serde_json::Value::Null);
+        assert_eq!(json["children"][0]["command"], "true");
+        assert_eq!(json["children"][0]["external"], false);
+        assert_eq!(json["children"][0]["children"], serde_json::Value::Null);
+        assert_eq!(json["children"][1]["children"], serde_json::json!([]));
+    }
+}
@@ -4,24 +4,138 @@
 #[macro_use]
 extern crate pretty_assertions;
 
-#[macro_use]
-extern crate lazy_static;
+use std::path::Path;
+use std::process;
+
+use clap::Parser;
 
+use config_reader::config_aggregator;
+use config_reader::config_checker::{self, CheckSeverity};
+use config_reader::config_schema;
 use config_reader::config_structs::AppConfig;
+use config_reader::config_watcher::ConfigWatcher;
 
+use crate::cli::{Cli, CliCommand};
 use crate::ui::state::UiState;
 
+mod cli;
 mod config_reader;
+mod executor;
+mod headless;
 mod ui;
 
-lazy_static! {
-    static ref APP_CONFIG: AppConfig = config_reader::toml_parser::read_config();
+/// Reads the config (from `config_path`, if it is set), and if there is any problem with it
+/// then prints all of them and exits before the UI is even started.
+fn read_config_or_exit(config_path: Option<&Path>) -> AppConfig {
+    match config_reader::toml_parser::read_config(config_path) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprint!("{}", errors);
+            process::exit(1);
+        }
+    }
 }
 
 fn main() {
-    let ui_state = UiState::new(&APP_CONFIG.config_tree);
+    let cli = Cli::parse();
+    let config_path = cli.config.as_deref();
+
+    match cli.command {
+        Some(CliCommand::Check) => process::exit(check_config(config_path)),
+        Some(CliCommand::Schema) => {
+            let schema = config_schema::config_json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        }
+        Some(CliCommand::List { path, json }) => {
+            let mut config = read_config_or_exit(config_path);
+
+            match headless::list_nodes(&mut config, path.as_deref(), json) {
+                Ok(tree) => print!("{}", tree),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(CliCommand::Run {
+            path,
+            args,
+            yes,
+            extra_args,
+        }) => {
+            let mut config = read_config_or_exit(config_path);
+
+            match headless::run_command(&mut config, &path, &args, yes, &extra_args) {
+                Ok(code) => process::exit(code),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => show_ui(config_path),
+    }
+}
+
+/// Implementation of `eddie check`. Prints where the config was searched for, every problem
+/// found in it, and returns the exit code of the process.
+fn check_config(config_path: Option<&Path>) -> i32 {
+    let config = match config_reader::toml_parser::read_config(config_path) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprint!("{}", errors);
+            return 1;
+        }
+    };
+
+    println!("Config search order:");
+    for candidate in config_aggregator::config_search_order(config_path) {
+        let is_used = candidate.path.canonicalize().ok().as_ref() == Some(&config.config_location);
+        println!("  {} {}", if is_used { "*" } else { " " }, candidate);
+    }
+
+    for issue in config.issues() {
+        println!("warning: {}", issue);
+    }
+
+    let issues = config_checker::check_config(&config);
+    let mut error_count = 0;
+
+    for issue in &issues {
+        match issue.severity() {
+            CheckSeverity::Error => {
+                error_count += 1;
+                println!("error: {}", issue);
+            }
+            CheckSeverity::Warning => println!("warning: {}", issue),
+        }
+    }
+
+    let warning_count = config.issues().len() + issues.len() - error_count;
+    println!(
+        "Found {} error(s) and {} warning(s)",
+        error_count, warning_count
+    );
+
+    if error_count > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn show_ui(config_path: Option<&Path>) {
+    let config = read_config_or_exit(config_path);
+    let mut ui_state = UiState::new(&config.config_tree);
+    ui_state.config_watcher = Some(ConfigWatcher::new(config.watched_paths.clone()));
+
+    // show any problem found while reading the config files where the user can see it
+    let issues = config.issues();
+    if !issues.is_empty() {
+        ui_state.command_output = format!("Config issues:\n{}", issues.join("\n"));
+    }
 
-    ui::show_ui(ui_state).unwrap();
+    ui::show_ui(ui_state, config).unwrap();
 
     // clear terminal when we exit
     print!("{esc}c", esc = 27 as char);
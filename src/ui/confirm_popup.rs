@@ -0,0 +1,167 @@
+//! The confirm popup asks the user to confirm a command which sets `confirm` (see
+//! [Confirmation]) before executing it.
+
+use std::collections::HashMap;
+
+use crate::config_reader::config_structs::{ConfigNode, Confirmation};
+use crate::config_reader::config_tree::NodeId;
+
+/// How many lines of a script are shown in the popup.
+const SCRIPT_LINES: usize = 8;
+
+pub struct ConfirmPopup {
+    /// The command waiting to be confirmed.
+    pub node: NodeId,
+    pub confirmation: Confirmation,
+    /// The values of the arguments of the command, if it has any.
+    pub arg_values: HashMap<String, String>,
+    /// What the user typed so far, when a word has to be typed.
+    pub typed: String,
+    /// Why the command wasn't executed yet, if the user tried to confirm it.
+    pub error: Option<String>,
+}
+
+impl ConfirmPopup {
+    /// Creates the popup for `node`, whose ID is `id`, which must have a `confirm`.
+    pub fn new(id: NodeId, node: &ConfigNode, arg_values: HashMap<String, String>) -> ConfirmPopup {
+        ConfirmPopup {
+            node: id,
+            confirmation: node.confirm.clone().unwrap_or(Confirmation::Yes),
+            arg_values,
+            typed: String::new(),
+            error: None,
+        }
+    }
+
+    /// The word the user has to type, if `y` isn't enough.
+    pub fn word(&self) -> Option<&str> {
+        match &self.confirmation {
+            Confirmation::Word(word) => Some(word),
+            Confirmation::Yes => None,
+        }
+    }
+
+    /// Handles the character `c` typed by the user, and returns whether the command is
+    /// confirmed by it. Only `y` confirms the command when no word has to be typed.
+    pub fn push_char(&mut self, c: char) -> bool {
+        if self.word().is_some() {
+            self.typed.push(c);
+            false
+        } else {
+            c == 'y' || c == 'Y'
+        }
+    }
+
+    /// Returns the lines of the script of `node` shown below its command line, which only
+    /// invokes the interpreter: the first [SCRIPT_LINES] of them, followed by how many more
+    /// there are. Commands which aren't scripts have none.
+    pub fn script_lines(node: &ConfigNode) -> Vec<String> {
+        let script = match &node.script {
+            Some(script) => script.trim_end(),
+            None => return vec![],
+        };
+
+        let mut lines: Vec<String> = script
+            .lines()
+            .take(SCRIPT_LINES)
+            .map(String::from)
+            .collect();
+        let hidden = script.lines().count().saturating_sub(SCRIPT_LINES);
+        if hidden > 0 {
+            lines.push(format!("... ({} more lines)", hidden));
+        }
+
+        lines
+    }
+
+    pub fn pop_char(&mut self) {
+        self.typed.pop();
+    }
+
+    /// Checks that the right word was typed, if one has to be. If it wasn't then `false` is
+    /// returned and the typed text is cleared.
+    pub fn submit(&mut self) -> bool {
+        let expected = match self.word() {
+            Some(word) => word.to_string(),
+            None => {
+                self.error = Some("Press \"y\" to run the command".to_string());
+                return false;
+            }
+        };
+
+        if self.typed == expected {
+            self.error = None;
+            true
+        } else {
+            self.typed.clear();
+            self.error = Some(format!("Type \"{}\" exactly to run the command", expected));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config_reader::config_tree::ConfigTree;
+
+    use super::*;
+
+    fn popup(confirm: Confirmation) -> ConfirmPopup {
+        let node = ConfigNode {
+            name: "destroy".to_string(),
+            command: "terraform destroy".to_string(),
+            confirm: Some(confirm),
+            ..Default::default()
+        };
+        let tree = ConfigTree::new(node);
+
+        ConfirmPopup::new(tree.root(), tree.node(tree.root()), HashMap::new())
+    }
+
+    #[test]
+    fn test_confirm_with_y() {
+        let mut popup = popup(Confirmation::Yes);
+
+        assert!(!popup.push_char('n'));
+        assert!(!popup.submit());
+        assert!(popup.error.is_some());
+        assert!(popup.push_char('y'));
+    }
+
+    #[test]
+    fn test_confirm_with_a_word() {
+        let mut popup = popup(Confirmation::Word("prod".to_string()));
+
+        for c in "prud".chars() {
+            assert!(!popup.push_char(c));
+        }
+        assert!(!popup.submit());
+        assert_eq!(popup.typed, "");
+
+        "prodd".chars().for_each(|c| {
+            popup.push_char(c);
+        });
+        popup.pop_char();
+        assert!(popup.submit());
+        assert_eq!(popup.error, None);
+    }
+
+    #[test]
+    fn test_script_lines() {
+        let mut node = ConfigNode::default();
+        assert_eq!(ConfirmPopup::script_lines(&node), Vec::<String>::new());
+
+        node.script = Some("import sys\nprint(sys.argv)\n\n".to_string());
+        assert_eq!(
+            ConfirmPopup::script_lines(&node),
+            vec!["import sys", "print(sys.argv)"]
+        );
+
+        let long_script: Vec<_> = (0..10).map(|e| format!("echo {}", e)).collect();
+        node.script = Some(long_script.join("\n"));
+        let lines = ConfirmPopup::script_lines(&node);
+        assert_eq!(lines.len(), SCRIPT_LINES + 1);
+        assert_eq!(lines[SCRIPT_LINES - 1], "echo 7");
+        assert_eq!(lines[SCRIPT_LINES], "... (2 more lines)");
+    }
+}
@@ -1,26 +1,185 @@
 use tui::backend::Backend;
 use tui::style::{Color, Modifier, Style};
-use tui::text::{Span, Spans};
-use tui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+use tui::text::{Span, Spans, Text};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap};
 use tui::Frame;
 
-use crate::ui::layout::BasicAppLayout;
-use crate::ui::state::UiState;
+use crate::config_reader::config_structs::AppConfig;
+use crate::executor;
+use crate::ui::confirm_popup::ConfirmPopup;
+use crate::ui::input_form::InputForm;
+use crate::ui::layout::{self, BasicAppLayout};
+use crate::ui::state::{StatusMessage, UiState};
+
+/// Draws the form that asks for the arguments of a command on top of everything else.
+fn draw_input_form<B: Backend>(frame: &mut Frame<B>, form: &InputForm, config: &AppConfig) {
+    let node = config.config_tree.node(form.node);
+    let area = layout::centered_rect(60, 50, frame.size());
+
+    let mut lines: Vec<Spans> = form
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let is_selected = idx == form.selected;
+            let mut spans = vec![
+                Span::raw(if is_selected { ">> " } else { "   " }),
+                Span::styled(
+                    format!(
+                        "{}{}: ",
+                        field.arg.prompt,
+                        if field.arg.required { " *" } else { "" }
+                    ),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    field.value.clone(),
+                    if is_selected {
+                        Style::default().fg(Color::Black).bg(Color::Green)
+                    } else {
+                        Style::default()
+                    },
+                ),
+            ];
+
+            if !field.arg.choices.is_empty() {
+                spans.push(Span::styled(
+                    format!("  (LEFT/RIGHT: {})", field.arg.choices.join(" | ")),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+
+            Spans::from(spans)
+        })
+        .collect();
+
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(format!(
+        "Command: {}",
+        executor::resolve_command(node, &form.values(), &config.eddie_config)
+    )));
+
+    if let Some(error) = &form.error {
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        "TAB to select next field / RETURN to run / ESC to cancel",
+        Style::default().fg(Color::Yellow),
+    )));
+
+    let block = Block::default()
+        .title(format!("Arguments for \"{}\"", node.name))
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Draws the popup asking to confirm a command on top of everything else.
+fn draw_confirm_popup<B: Backend>(frame: &mut Frame<B>, popup: &ConfirmPopup, config: &AppConfig) {
+    let node = config.config_tree.node(popup.node);
+    let area = layout::centered_rect(60, 40, frame.size());
+
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            "This command has to be confirmed before it runs:",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(""),
+        Spans::from(executor::resolve_command(
+            node,
+            &popup.arg_values,
+            &config.eddie_config,
+        )),
+        Spans::from(""),
+    ];
+
+    // the command line of a script only shows the interpreter, so show the script too
+    let script_lines = ConfirmPopup::script_lines(node);
+    if !script_lines.is_empty() {
+        lines.push(Spans::from("Script:"));
+        lines.extend(
+            script_lines
+                .into_iter()
+                .map(|e| Spans::from(Span::styled(e, Style::default().fg(Color::DarkGray)))),
+        );
+        lines.push(Spans::from(""));
+    }
+
+    match popup.word() {
+        Some(word) => {
+            lines.push(Spans::from(format!("Type \"{}\" to confirm:", word)));
+            lines.push(Spans::from(vec![
+                Span::raw(">> "),
+                Span::styled(
+                    popup.typed.clone(),
+                    Style::default().fg(Color::Black).bg(Color::Green),
+                ),
+            ]));
+        }
+        None => lines.push(Spans::from("Press \"y\" to confirm.")),
+    }
+
+    if let Some(error) = &popup.error {
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        if popup.word().is_some() {
+            "RETURN to run / ESC to cancel"
+        } else {
+            "y to run / ESC to cancel"
+        },
+        Style::default().fg(Color::Yellow),
+    )));
+
+    let block = Block::default()
+        .title(format!("Run \"{}\"?", node.name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+pub fn draw_tui<B: Backend>(
+    frame: &mut Frame<B>,
+    layout: &BasicAppLayout,
+    state: &mut UiState,
+    config: &AppConfig,
+) {
+    let tree = &config.config_tree;
 
-pub fn draw_tui<B: Backend>(frame: &mut Frame<B>, layout: &BasicAppLayout, state: &mut UiState) {
     {
         // render breadcrumbs
         let block = Block::default().borders(Borders::BOTTOM | Borders::TOP);
         let flat_bread: String = state
             .breadcrumbs
             .iter()
-            .map(|e| e.name.clone())
+            .map(|&e| tree.node(e).name.clone())
             .collect::<Vec<String>>()
             .join(" / ");
 
         let bc_text = format!(
             "{} > {}",
-            &crate::APP_CONFIG.eddie_config.ship_name,
+            &config.eddie_config.ship_name,
             &flat_bread
         );
         let paragraph = Paragraph::new(bc_text).block(block);
@@ -30,7 +189,7 @@ pub fn draw_tui<B: Backend>(frame: &mut Frame<B>, layout: &BasicAppLayout, state
     {
         // Render title
         let block = Block::default().borders(Borders::ALL);
-        let paragraph = Paragraph::new(Span::from(state.title))
+        let paragraph = Paragraph::new(Span::from(tree.node(state.current_node).name.as_str()))
             .style(
                 Style::default()
                     .add_modifier(Modifier::BOLD)
@@ -45,23 +204,43 @@ pub fn draw_tui<B: Backend>(frame: &mut Frame<B>, layout: &BasicAppLayout, state
         // Render list items
         let block = Block::default().title("Group items").borders(Borders::ALL);
 
-        let items: Vec<_> = state
+        let mut items: Vec<_> = state
             .group_items_state
             .items
             .iter()
-            .enumerate()
-            .map(|(idx, i)| {
-                let lines = vec![Spans::from(*i)];
-                let is_leaf = state.group_items[idx].is_leaf();
+            .map(|&id| {
+                let node = tree.node(id);
+                let lines = vec![Spans::from(node.name.as_str())];
+                let is_leaf = !tree.is_group(id);
 
-                ListItem::new(lines).style(
+                // nodes which are not available on this machine are greyed out
+                let style = if node.unavailable.is_some() {
+                    Style::default().fg(Color::Gray).bg(Color::DarkGray)
+                } else {
                     Style::default()
                         .fg(if is_leaf { Color::Black } else { Color::White })
-                        .bg(if is_leaf { Color::Green } else { Color::Blue }),
-                )
+                        .bg(if is_leaf { Color::Green } else { Color::Blue })
+                };
+
+                ListItem::new(lines).style(style)
             })
             .collect();
 
+        // the generated children are added once the generator is done, and the generators
+        // of untrusted groups wait for `g`
+        let current_node = tree.node(state.current_node);
+        if state.generations.contains_key(&state.current_node) {
+            items.push(ListItem::new("Loading...").style(Style::default().fg(Color::DarkGray)));
+        } else if current_node.untrusted
+            && current_node.unavailable.is_none()
+            && tree.needs_generation(state.current_node)
+        {
+            items.push(
+                ListItem::new("Press \"g\" to generate the items")
+                    .style(Style::default().fg(Color::DarkGray)),
+            );
+        }
+
         let lsst = List::new(items)
             .block(block)
             .style(Style::default().fg(Color::White))
@@ -85,31 +264,67 @@ pub fn draw_tui<B: Backend>(frame: &mut Frame<B>, layout: &BasicAppLayout, state
 
     {
         // Render Item description
-        let node_type = if state.get_selected_node().unwrap().is_leaf() {
+        let selected_id = state.get_selected_id().unwrap_or(state.current_node);
+        let selected_node = tree.node(selected_id);
+        let node_type = if !tree.is_group(selected_id) {
             "Command"
         } else {
             "Group"
         };
 
+        let mut description = Text::from(state.description(tree));
+        if let Some(reason) = &selected_node.unavailable {
+            description.extend(Text::styled(
+                format!("\nNot available here: {}", reason),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        if selected_node.preview_command.is_some() && selected_node.unavailable.is_none() {
+            let key_path = &selected_node.key_path;
+            let output = match state.previews.output(key_path) {
+                Some(output) => output,
+                None if selected_node.untrusted && !state.previews.is_running(key_path) => {
+                    "Press \"p\" to run the preview"
+                }
+                None => "Loading...",
+            };
+            description.extend(Text::styled("\nPreview:", Style::default().fg(Color::Cyan)));
+            description.extend(Text::raw(output.to_string()));
+        }
+
         let block = Block::default()
             .title(format!("Item description (Type: {})", node_type))
             .borders(Borders::ALL);
-        let paragraph = Paragraph::new(state.description)
+        let paragraph = Paragraph::new(description)
             .wrap(Wrap { trim: true })
             .block(block);
         frame.render_widget(paragraph, layout.item_description);
     }
 
     {
-        // render help message
+        // render help message, or the status message if there is one
+        let (text, color) = match &state.status {
+            Some(StatusMessage::Info(message)) => (message.as_str(), Color::Green),
+            Some(StatusMessage::Error(message)) => (message.as_str(), Color::Red),
+            None => ("TAB to select next / Shift + TAB to select previous / RETURN to select / BACKSPACE to go back / r to reload the config / g to refresh generated items / p to preview", Color::Yellow),
+        };
+
         let block = Block::default().borders(Borders::NONE);
-        let paragraph = Paragraph::new(Span::from("TAB to select next / Shift + TAB to select previous / RETURN to select / BACKSPACE to go back"))
+        let paragraph = Paragraph::new(Span::from(text))
             .style(Style::default()
                        .add_modifier(Modifier::BOLD)
-                       .fg(Color::Yellow)
+                       .fg(color)
                    // .bg(Color::DarkGray)
             )
             .block(block);
         frame.render_widget(paragraph, layout.help_content);
     }
+
+    if let Some(form) = &state.input_form {
+        draw_input_form(frame, form, config);
+    }
+
+    if let Some(popup) = &state.confirm_popup {
+        draw_confirm_popup(frame, popup, config);
+    }
 }
@@ -1,87 +1,344 @@
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::sync::mpsc::TryRecvError;
+use std::time::Instant;
 
 use crossterm::event::KeyCode;
 
+use crate::config_reader::config_structs::{AppConfig, ConfigNode};
+use crate::config_reader::config_tree::NodeId;
+use crate::config_reader::toml_parser;
+use crate::executor::execute_command;
+use crate::executor::generator;
+use crate::executor::preview;
+use crate::ui::confirm_popup::ConfirmPopup;
+use crate::ui::input_form::InputForm;
+use crate::ui::state::{StatusMessage, UiState};
 use crate::ui::util::event::Event;
-use crate::{config_reader::config_structs::ConfigNode, ui::state::UiState};
 
-fn execute_command(command_node: &ConfigNode) -> String {
-    let mut command = Command::new(if command_node.opens_external {
-        &crate::APP_CONFIG.eddie_config.terminal_emulator
-    } else {
-        &crate::APP_CONFIG.eddie_config.shell
-    });
-
-    if command_node.opens_external {
-        command
-            .arg(&crate::APP_CONFIG.eddie_config.terminal_emulator_command_arg)
-            .arg(&crate::APP_CONFIG.eddie_config.shell)
-            .arg("-c")
-            .arg(&command_node.command)
+fn run_command(
+    state: &mut UiState,
+    config: &AppConfig,
+    node: NodeId,
+    arg_values: &HashMap<String, String>,
+) {
+    state.command_output = "".to_string();
+
+    state.command_output = execute_command(
+        config.config_tree.node(node),
+        arg_values,
+        &config.eddie_config,
+    );
+
+    // always triggered a forced redraw after a command is executed
+    state.need_redraw = true;
+}
+
+/// Reads the config files again and shows the new tree. If the config has problems then
+/// they're shown in the command output, and the current tree is kept.
+fn reload_config(state: &mut UiState, config: &mut AppConfig) {
+    match toml_parser::read_config(config.config_override.as_deref()) {
+        Ok(new_config) => {
+            state.reload(&config.config_tree, &new_config.config_tree);
+            *config = new_config;
+
+            if let Some(watcher) = &mut state.config_watcher {
+                watcher.watch(config.watched_paths.clone());
+            }
+            generate_children(state, config, false);
+
+            let issues = config.issues();
+            state.status = Some(StatusMessage::Info(format!(
+                "Config reloaded with {} issue(s)",
+                issues.len()
+            )));
+            if !issues.is_empty() {
+                state.command_output = format!("Config issues:\n{}", issues.join("\n"));
+            }
+        }
+        Err(errors) => {
+            state.status = Some(StatusMessage::Error(format!(
+                "The config wasn't reloaded, it has {} problem(s) (see the command output)",
+                errors.0.len()
+            )));
+            state.command_output = errors.to_string();
+        }
+    }
+
+    state.need_redraw = true;
+}
+
+/// Starts the generator of the current group in the background if it has one (see
+/// [generator::start_generation]), unless it is already running or the children it has are
+/// still fresh and `force` isn't set. Generators of unavailable groups are not run, and those
+/// of untrusted groups only run when `force` is set.
+fn generate_children(state: &mut UiState, config: &AppConfig, force: bool) {
+    let group = state.current_node;
+    let tree = &config.config_tree;
+    let node = tree.node(group);
+
+    if node.generator.is_none() || node.unavailable.is_some() || (node.untrusted && !force) {
+        return;
+    }
+    if state.generations.contains_key(&group) || (!force && !tree.needs_generation(group)) {
+        return;
+    }
+
+    state.generations.insert(
+        group,
+        generator::start_generation(node, &config.eddie_config),
+    );
+}
+
+/// Replaces the generated children of the groups whose generator finished. If the current
+/// group is one of them then its list is updated, and the selected node stays selected if it
+/// is generated again. Popups open on a generated command which is gone are closed, since its
+/// ID may now be used by another node.
+fn collect_generated_children(state: &mut UiState, config: &mut AppConfig) {
+    let mut finished = vec![];
+
+    for (&group, receiver) in &state.generations {
+        match receiver.try_recv() {
+            Ok(result) => finished.push((group, result)),
+            Err(TryRecvError::Disconnected) => {
+                finished.push((group, Err("the generator stopped unexpectedly".to_string())))
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+
+    let tree = &mut config.config_tree;
+    for (group, result) in finished {
+        state.generations.remove(&group);
+
+        let children = match result {
+            Ok(c) => c,
+            Err(error) => {
+                state.command_output = format!(
+                    "The items of \"{}\" couldn't be generated: {}",
+                    tree.node(group).name,
+                    error
+                );
+                continue;
+            }
+        };
+
+        let selected = state.get_selected_node(tree).map(|e| e.key_path.clone());
+        let key_path_of = |id: NodeId| (id, tree.node(id).key_path.clone());
+        let form_node = state.input_form.as_ref().map(|e| key_path_of(e.node));
+        let popup_node = state.confirm_popup.as_ref().map(|e| key_path_of(e.node));
+
+        tree.set_generated_children(group, children);
+
+        let is_gone = |node: Option<(NodeId, String)>| {
+            node.is_some_and(|(id, key_path)| tree.find(&key_path) != Some(id))
+        };
+        if is_gone(form_node) || is_gone(popup_node) {
+            state.input_form = None;
+            state.confirm_popup = None;
+            state.status = Some(StatusMessage::Info(
+                "The command is gone since its group was generated again".to_string(),
+            ));
+        }
+
+        if group == state.current_node {
+            state.set_config_for_node(tree, group);
+            if let Some(selected) = selected {
+                state.select_key_path(tree, &selected);
+            }
+        }
+    }
+}
+
+/// The node whose preview is shown in the item description, if it has one which can run.
+/// Like in the item description, this is the current group if it is empty. Previews of
+/// unavailable nodes are not run.
+fn previewed_node<'c>(state: &UiState, config: &'c AppConfig) -> Option<&'c ConfigNode> {
+    let tree = &config.config_tree;
+
+    Some(
+        state
+            .get_selected_node(tree)
+            .unwrap_or_else(|| tree.node(state.current_node)),
+    )
+    .filter(|e| e.preview_command.is_some() && e.unavailable.is_none())
+}
+
+/// Starts the preview of the selected node once it has stayed selected for a moment, and
+/// collects the outputs of the previews which finished (see [crate::ui::preview]). Previews of
+/// untrusted nodes only run when `p` is pressed.
+fn update_preview(state: &mut UiState, config: &AppConfig) {
+    let now = Instant::now();
+    let selected = previewed_node(state, config).filter(|e| !e.untrusted);
+
+    state
+        .previews
+        .select(selected.map(|e| e.key_path.as_str()), now, || {
+            preview::start_preview(selected.unwrap(), &config.eddie_config)
+        });
+    state.previews.collect(now);
+}
+
+/// Runs `node`, unless it has to be confirmed first: then the confirm popup is opened.
+fn request_command(
+    state: &mut UiState,
+    config: &AppConfig,
+    node: NodeId,
+    arg_values: HashMap<String, String>,
+) {
+    let command_node = config.config_tree.node(node);
+
+    if command_node.confirm.is_some() {
+        state.confirm_popup = Some(ConfirmPopup::new(node, command_node, arg_values));
     } else {
-        command.arg("-c").arg(&command_node.command)
+        run_command(state, config, node, &arg_values);
+    }
+}
+
+/// Handles the keys pressed while the confirm popup of a command is open.
+fn handle_confirm_input(input: KeyCode, state: &mut UiState, config: &AppConfig) {
+    let popup = match &mut state.confirm_popup {
+        Some(p) => p,
+        None => return,
     };
 
-    let child = command
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect(&format!(
-            "failed to execute process. Command: {:?}",
-            command
-        ));
-
-    // if the process opens an external terminal then don't wait for output
-    if command_node.opens_external {
-        return String::from("Executing command in external terminal...");
-    } else {
-        match child.wait_with_output() {
-            Ok(o) => String::from_utf8(o.stdout).unwrap(),
-            Err(_) => "There was an error while decoding the command's output!".to_string(),
+    let confirmed = match input {
+        KeyCode::Esc => {
+            state.confirm_popup = None;
+            state.command_output = "Command cancelled".to_string();
+            return;
+        }
+        KeyCode::Enter => popup.submit(),
+        KeyCode::Backspace => {
+            popup.pop_char();
+            false
+        }
+        KeyCode::Char(c) => popup.push_char(c),
+        _ => false,
+    };
+
+    if confirmed {
+        if let Some(popup) = state.confirm_popup.take() {
+            run_command(state, config, popup.node, &popup.arg_values);
+        }
+    }
+}
+
+/// Handles the keys pressed while the input form of a command is open.
+fn handle_form_input(input: KeyCode, state: &mut UiState, config: &AppConfig) {
+    let form = match &mut state.input_form {
+        Some(f) => f,
+        None => return,
+    };
+
+    match input {
+        KeyCode::Esc => {
+            state.input_form = None;
+        }
+        KeyCode::Enter if form.validate() => {
+            let node = form.node;
+            let values = form.values();
+
+            state.input_form = None;
+            request_command(state, config, node, values);
         }
+        KeyCode::Tab | KeyCode::Down => form.next_field(),
+        KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+        KeyCode::Right => form.next_choice(),
+        KeyCode::Left => form.previous_choice(),
+        KeyCode::Backspace => form.pop_char(),
+        KeyCode::Char(c) => form.push_char(c),
+        _ => {}
     }
 }
 
-pub fn handle_event(ev: Event<KeyCode>, state: &mut UiState) -> Option<bool> {
+pub fn handle_event(
+    ev: Event<KeyCode>,
+    state: &mut UiState,
+    config: &mut AppConfig,
+) -> Option<bool> {
+    // informational messages are dismissed by the next key, errors stay until they're fixed
+    if let (Event::Input(_), Some(StatusMessage::Info(_))) = (&ev, &state.status) {
+        state.status = None;
+    }
+
+    let tree = &config.config_tree;
+
     match ev {
+        // while a popup is open it gets all the keys
+        Event::Input(input) if state.confirm_popup.is_some() => {
+            handle_confirm_input(input, state, config)
+        }
+        Event::Input(input) if state.input_form.is_some() => {
+            handle_form_input(input, state, config)
+        }
         Event::Input(input) => match input {
             KeyCode::Char('q') => {
                 return Some(true);
             }
+            KeyCode::Char('r') => reload_config(state, config),
+            KeyCode::Char('p') => match previewed_node(state, config) {
+                Some(node) => state.previews.run(&node.key_path, || {
+                    preview::start_preview(node, &config.eddie_config)
+                }),
+                None => {
+                    state.status = Some(StatusMessage::Info("This item has no preview".to_string()))
+                }
+            },
+            KeyCode::Char('g') => {
+                if tree.node(state.current_node).generator.is_some() {
+                    generate_children(state, config, true);
+                } else {
+                    state.status = Some(StatusMessage::Info(
+                        "This group has no generator to refresh its items".to_string(),
+                    ));
+                }
+            }
             KeyCode::Backspace => {
                 // state.current_group_items_state.unselect();
-                state.exit_current_node();
+                state.exit_current_node(tree);
+                generate_children(state, config, false);
             }
             KeyCode::Tab => {
                 state.group_items_state.next();
-                state.update_description();
             }
             KeyCode::BackTab => {
                 state.group_items_state.previous();
-                state.update_description();
             }
             KeyCode::Enter => {
-                let selected_node = state.get_selected_node()?;
-                if selected_node.is_leaf() {
-                    state.command_output = "".to_string();
-
-                    state.command_output = execute_command(&selected_node);
-
-                    // always triggered a forced redraw after a command is executed
-                    state.need_redraw = true;
+                let selected_id = state.get_selected_id()?;
+                let selected_node = tree.node(selected_id);
+                if !tree.is_group(selected_id) {
+                    if let Some(reason) = &selected_node.unavailable {
+                        state.command_output =
+                            format!("This command is not available here: {}", reason);
+                    } else if selected_node.args.is_empty() {
+                        request_command(state, config, selected_id, HashMap::new());
+                    } else {
+                        // ask for the values of the arguments before running the command
+                        state.input_form = Some(InputForm::new(selected_id, selected_node));
+                    }
                 } else {
                     // this is used to "action" on the selected item
-                    state.enter_selected_node();
+                    state.enter_selected_node(tree);
+                    generate_children(state, config, false);
                 }
             }
-            KeyCode::Esc => {
-                // this can be used to exit context menu like popup for input
-                // For input example see: https://github.com/fdehau/tui-rs/blob/master/examples/user_input.rs
-            }
             _ => {}
         },
-        Event::Tick => {}
+        Event::Tick => {
+            let config_changed = state
+                .config_watcher
+                .as_mut()
+                .is_some_and(|e| e.has_changed());
+
+            if config_changed {
+                reload_config(state, config);
+            }
+        }
     };
 
+    collect_generated_children(state, config);
+    update_preview(state, config);
+
     Some(false)
 }
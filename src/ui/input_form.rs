@@ -0,0 +1,184 @@
+//! The input form is a popup used to ask the user for the values of the arguments of a
+//! command (see [CommandArg]) before executing it.
+
+use std::collections::HashMap;
+
+use crate::config_reader::config_structs::{CommandArg, ConfigNode};
+use crate::config_reader::config_tree::NodeId;
+
+pub struct FormField {
+    pub arg: CommandArg,
+    pub value: String,
+}
+
+impl FormField {
+    fn has_choices(&self) -> bool {
+        !self.arg.choices.is_empty()
+    }
+
+    /// Selects the choice which is `offset` positions away from the current one.
+    fn cycle_choice(&mut self, offset: isize) {
+        let choices = &self.arg.choices;
+        let current = choices.iter().position(|e| *e == self.value).unwrap_or(0) as isize;
+        let next = (current + offset).rem_euclid(choices.len() as isize) as usize;
+
+        self.value = choices[next].clone();
+    }
+}
+
+pub struct InputForm {
+    /// The command whose arguments are being asked.
+    pub node: NodeId,
+    pub fields: Vec<FormField>,
+    /// Index of the field which is currently being edited.
+    pub selected: usize,
+    /// Why the values can't be submitted, if they were rejected.
+    pub error: Option<String>,
+}
+
+impl InputForm {
+    /// Creates the form for the arguments of `node`, whose ID is `id`.
+    pub fn new(id: NodeId, node: &ConfigNode) -> InputForm {
+        InputForm {
+            node: id,
+            fields: node
+                .args
+                .iter()
+                .map(|arg| FormField {
+                    arg: arg.clone(),
+                    value: arg.default.clone(),
+                })
+                .collect(),
+            selected: 0,
+            error: None,
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.selected = (self.selected + 1) % self.fields.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.selected = (self.selected + self.fields.len() - 1) % self.fields.len();
+    }
+
+    /// Types `c` into the selected field. Fields with choices can't be typed into.
+    pub fn push_char(&mut self, c: char) {
+        let field = &mut self.fields[self.selected];
+
+        if !field.has_choices() {
+            field.value.push(c);
+        }
+    }
+
+    pub fn pop_char(&mut self) {
+        let field = &mut self.fields[self.selected];
+
+        if !field.has_choices() {
+            field.value.pop();
+        }
+    }
+
+    pub fn next_choice(&mut self) {
+        let field = &mut self.fields[self.selected];
+
+        if field.has_choices() {
+            field.cycle_choice(1);
+        }
+    }
+
+    pub fn previous_choice(&mut self) {
+        let field = &mut self.fields[self.selected];
+
+        if field.has_choices() {
+            field.cycle_choice(-1);
+        }
+    }
+
+    /// Returns the value of every argument, keyed by argument name.
+    pub fn values(&self) -> HashMap<String, String> {
+        self.fields
+            .iter()
+            .map(|e| (e.arg.name.clone(), e.value.clone()))
+            .collect()
+    }
+
+    /// Checks that all the required fields have a value. If some don't then the first one
+    /// is selected and `false` is returned.
+    pub fn validate(&mut self) -> bool {
+        match self
+            .fields
+            .iter()
+            .position(|e| e.arg.required && e.value.is_empty())
+        {
+            Some(idx) => {
+                self.selected = idx;
+                self.error = Some(format!("\"{}\" is required", self.fields[idx].arg.prompt));
+                false
+            }
+            None => {
+                self.error = None;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config_reader::config_tree::ConfigTree;
+
+    use super::*;
+
+    fn form(args: Vec<CommandArg>) -> InputForm {
+        let node = ConfigNode {
+            name: "test".to_string(),
+            command: "echo {{a}} {{b}}".to_string(),
+            args,
+            ..Default::default()
+        };
+        let tree = ConfigTree::new(node);
+
+        InputForm::new(tree.root(), tree.node(tree.root()))
+    }
+
+    fn arg(name: &str, default: &str, choices: &[&str], required: bool) -> CommandArg {
+        CommandArg {
+            name: name.to_string(),
+            prompt: name.to_string(),
+            default: default.to_string(),
+            choices: choices.iter().map(|e| e.to_string()).collect(),
+            required,
+        }
+    }
+
+    #[test]
+    fn test_typing_and_choices() {
+        let mut form = form(vec![
+            arg("a", "x", &[], false),
+            arg("b", "dev", &["main", "dev"], false),
+        ]);
+
+        form.push_char('y');
+        form.next_field();
+        form.push_char('z');
+        form.next_choice();
+
+        let values = form.values();
+        assert_eq!(values["a"], "xy");
+        assert_eq!(values["b"], "main");
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let mut form = form(vec![arg("a", "x", &[], false), arg("b", "", &[], true)]);
+
+        assert!(!form.validate());
+        assert_eq!(form.selected, 1);
+        assert!(form.error.is_some());
+
+        form.push_char('v');
+        assert!(form.validate());
+        assert_eq!(form.error, None);
+    }
+}
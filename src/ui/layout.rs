@@ -45,4 +45,32 @@ pub fn create_layout<B: Backend>(f: &mut Frame<B>) -> BasicAppLayout {
         item_description: chunks[3],
         help_content: chunks[4]
     }
-}
\ No newline at end of file
+}
+
+/// Returns a rectangle centered inside `r`, which takes `percent_x`/`percent_y` of its size.
+/// Used to place popups.
+pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
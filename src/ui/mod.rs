@@ -4,16 +4,23 @@ use tui::{backend::CrosstermBackend, Terminal};
 
 use util::event::Events;
 
+use crate::config_reader::config_structs::AppConfig;
+use crate::executor;
 use crate::ui::state::UiState;
 
+mod confirm_popup;
 mod drawer;
 mod event_manager;
+mod input_form;
 mod layout;
+mod preview;
 pub mod state;
 #[allow(dead_code)]
 mod util;
 
-pub fn show_ui(mut state: UiState) -> Result<(), Box<dyn Error>> {
+/// Shows the UI for `config`, which is replaced whenever the config is reloaded, until the
+/// user quits.
+pub fn show_ui(mut state: UiState, mut config: AppConfig) -> Result<(), Box<dyn Error>> {
     // Terminal initialization
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -25,7 +32,7 @@ pub fn show_ui(mut state: UiState) -> Result<(), Box<dyn Error>> {
         terminal.draw(|frame| {
             let app_layout = layout::create_layout(frame);
 
-            drawer::draw_tui(frame, &app_layout, &mut state);
+            drawer::draw_tui(frame, &app_layout, &mut state, &config);
         })?;
 
         // force redraw if necessary
@@ -44,7 +51,7 @@ pub fn show_ui(mut state: UiState) -> Result<(), Box<dyn Error>> {
         }
 
         let ev = events.next()?;
-        match event_manager::event_handler::handle_event(ev, &mut state) {
+        match event_manager::event_handler::handle_event(ev, &mut state, &mut config) {
             None => break,
             Some(v) => {
                 if v {
@@ -54,5 +61,8 @@ pub fn show_ui(mut state: UiState) -> Result<(), Box<dyn Error>> {
         }
     }
 
+    // the scripts of the commands run in an external terminal are kept until now
+    executor::script::remove_kept_scripts();
+
     Ok(())
 }
@@ -0,0 +1,188 @@
+//! Keeps the outputs of the `preview_command` of the nodes (see [crate::executor::preview])
+//! shown in the item description. A preview only starts once its node has stayed selected for
+//! a moment, so that moving quickly through the list doesn't start one for every node, and
+//! its output is reused for a while when the node is selected again.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// How long a node must stay selected before its preview starts.
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(200);
+/// How long the output of a preview is reused before it runs again.
+const PREVIEW_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// The previews of the nodes, by key path.
+#[derive(Default)]
+pub struct Previews {
+    /// The selected node, if it has a preview, and since when it is selected.
+    selected: Option<(String, Instant)>,
+    /// The previews that are still running.
+    running: HashMap<String, Receiver<String>>,
+    /// The outputs of the previews that finished, and when they finished.
+    outputs: HashMap<String, (String, Instant)>,
+}
+
+impl Previews {
+    /// Tells that the node at `key_path` is selected (`None` if the selected node has no
+    /// preview). Its preview is started with `start` once it has been selected for long
+    /// enough, unless its last output is still fresh or it is already running.
+    pub fn select(
+        &mut self,
+        key_path: Option<&str>,
+        now: Instant,
+        start: impl FnOnce() -> Receiver<String>,
+    ) {
+        let key_path = match key_path {
+            Some(k) => k,
+            None => {
+                self.selected = None;
+                return;
+            }
+        };
+
+        match &self.selected {
+            Some((selected, _)) if selected == key_path => {}
+            _ => {
+                self.selected = Some((key_path.to_string(), now));
+                return;
+            }
+        }
+
+        let is_settled = self
+            .selected
+            .as_ref()
+            .is_some_and(|(_, since)| now.duration_since(*since) >= PREVIEW_DEBOUNCE);
+        let is_fresh = self
+            .outputs
+            .get(key_path)
+            .is_some_and(|(_, at)| now.duration_since(*at) < PREVIEW_CACHE_TTL);
+
+        if is_settled && !is_fresh && !self.running.contains_key(key_path) {
+            self.running.insert(key_path.to_string(), start());
+        }
+    }
+
+    /// Starts the preview of the node at `key_path` right away with `start`, unless it is
+    /// already running. This is how the previews which don't start by themselves (see
+    /// [crate::config_reader::config_structs::ConfigNode::untrusted]) are run.
+    pub fn run(&mut self, key_path: &str, start: impl FnOnce() -> Receiver<String>) {
+        if !self.running.contains_key(key_path) {
+            self.running.insert(key_path.to_string(), start());
+        }
+    }
+
+    /// Whether the preview of the node at `key_path` is running.
+    pub fn is_running(&self, key_path: &str) -> bool {
+        self.running.contains_key(key_path)
+    }
+
+    /// Collects the outputs of the previews that finished. Returns whether there was any.
+    pub fn collect(&mut self, now: Instant) -> bool {
+        let mut finished = vec![];
+
+        for (key_path, receiver) in &self.running {
+            match receiver.try_recv() {
+                Ok(output) => finished.push((key_path.clone(), output)),
+                Err(TryRecvError::Disconnected) => {
+                    finished.push((key_path.clone(), "The preview failed".to_string()))
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        for (key_path, output) in &finished {
+            self.running.remove(key_path);
+            self.outputs.insert(key_path.clone(), (output.clone(), now));
+        }
+
+        !finished.is_empty()
+    }
+
+    /// The last output of the preview of the node at `key_path`, even if it is running again.
+    pub fn output(&self, key_path: &str) -> Option<&str> {
+        self.outputs.get(key_path).map(|(e, _)| e.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// A preview which has already finished with `output`.
+    fn finished(output: &str) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        sender.send(output.to_string()).unwrap();
+        receiver
+    }
+
+    #[test]
+    fn test_previews_start_once_the_selection_settles() {
+        let mut previews = Previews::default();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // moving through the list doesn't start anything
+        previews.select(Some("a"), at(0), || panic!("a was only passed through"));
+        previews.select(Some("b"), at(100), || panic!("b was only passed through"));
+        previews.select(Some("c"), at(150), || panic!("c was just selected"));
+        previews.select(Some("c"), at(300), || panic!("c was selected too recently"));
+        previews.select(Some("c"), at(350), || finished("c output"));
+
+        assert!(previews.collect(at(350)));
+        assert_eq!(previews.output("c"), Some("c output"));
+        assert_eq!(previews.output("b"), None);
+        assert!(!previews.collect(at(400)));
+    }
+
+    #[test]
+    fn test_previews_are_cached_briefly() {
+        let mut previews = Previews::default();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        previews.select(Some("a"), at(0), || panic!("a was just selected"));
+        previews.select(Some("a"), at(200), || finished("first"));
+        previews.collect(at(200));
+
+        // coming back to it soon after reuses its output
+        previews.select(None, at(300), || unreachable!());
+        previews.select(Some("a"), at(400), || unreachable!());
+        previews.select(Some("a"), at(600), || panic!("a is still fresh"));
+        assert_eq!(previews.output("a"), Some("first"));
+
+        // the old output is shown until the new one is there
+        let (sender, receiver) = mpsc::channel();
+        previews.select(Some("a"), at(20_000), || receiver);
+        assert!(!previews.collect(at(20_000)));
+        assert_eq!(previews.output("a"), Some("first"));
+
+        sender.send("second".to_string()).unwrap();
+        assert!(previews.collect(at(20_100)));
+        assert_eq!(previews.output("a"), Some("second"));
+    }
+
+    #[test]
+    fn test_previews_run_on_demand() {
+        let mut previews = Previews::default();
+        let start = Instant::now();
+
+        let (sender, receiver) = mpsc::channel();
+        previews.run("a", || receiver);
+        previews.run("a", || panic!("a is already running"));
+        assert!(previews.is_running("a"));
+        assert_eq!(previews.output("a"), None);
+
+        sender.send("output".to_string()).unwrap();
+        assert!(previews.collect(start));
+        assert!(!previews.is_running("a"));
+        assert_eq!(previews.output("a"), Some("output"));
+
+        // even if its output is fresh, it runs again when asked for
+        previews.run("a", || finished("again"));
+        previews.collect(start);
+        assert_eq!(previews.output("a"), Some("again"));
+    }
+}
@@ -1,103 +1,182 @@
 use core::fmt;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
 
 use crate::config_reader::config_structs::ConfigNode;
+use crate::config_reader::config_tree::{ConfigTree, NodeId};
+use crate::config_reader::config_watcher::ConfigWatcher;
+use crate::ui::confirm_popup::ConfirmPopup;
+use crate::ui::input_form::InputForm;
+use crate::ui::preview::Previews;
 use crate::ui::util::StatefulList;
 
+/// A short message shown in the status bar, at the bottom of the UI.
+#[derive(Debug, PartialEq)]
+pub enum StatusMessage {
+    Info(String),
+    Error(String),
+}
+
+/// The state of the UI. Nodes are referred to by their [NodeId] in the [ConfigTree] that is
+/// being shown, which is passed to the methods that need it.
 pub struct UiState {
-    pub title: &'static str,
-    pub description: &'static str,
-    pub breadcrumbs: Vec<&'static ConfigNode>,
-    pub group_items_state: StatefulList<&'static str>,
-    pub group_items: Vec<&'static ConfigNode>,
-    pub current_node: &'static ConfigNode,
-    pub root_node: &'static ConfigNode,
+    /// The groups that were entered to get to `current_node`, starting from the root.
+    pub breadcrumbs: Vec<NodeId>,
+    /// The children of `current_node`.
+    pub group_items_state: StatefulList<NodeId>,
+    /// The group whose children are shown.
+    pub current_node: NodeId,
     pub command_output: String,
     pub need_redraw: bool,
+    /// The form asking for the arguments of a command, if it is open.
+    pub input_form: Option<InputForm>,
+    /// The popup asking to confirm a command, if it is open.
+    pub confirm_popup: Option<ConfirmPopup>,
+    /// Tells when the config files change. If `None` then the config is only reloaded on
+    /// request.
+    pub config_watcher: Option<ConfigWatcher>,
+    pub status: Option<StatusMessage>,
+    /// The outputs of the `preview_command` of the nodes, shown in the item description.
+    pub previews: Previews,
+    /// The generators which are running, by group (see
+    /// [crate::executor::generator::start_generation]).
+    pub generations: HashMap<NodeId, Receiver<Result<Vec<ConfigNode>, String>>>,
 }
 
-impl<'a> UiState {
-    pub fn new(root_node: &'static ConfigNode) -> UiState {
+impl UiState {
+    pub fn new(tree: &ConfigTree) -> UiState {
         let mut state = UiState {
-            title: "",
-            description: "",
             breadcrumbs: vec![],
-            current_node: root_node,
-            group_items: vec![],
+            current_node: tree.root(),
             group_items_state: StatefulList::new(),
             command_output: "".to_string(),
-            root_node,
             need_redraw: false,
+            input_form: None,
+            confirm_popup: None,
+            config_watcher: None,
+            status: None,
+            previews: Previews::default(),
+            generations: HashMap::new(),
         };
 
-        state.set_config_for_node(root_node);
+        state.set_config_for_node(tree, tree.root());
         state
     }
 
-    pub fn set_config_for_node(&mut self, node: &'static ConfigNode) {
-        self.title = &node.name;
-        self.description = &node.description;
+    pub fn set_config_for_node(&mut self, tree: &ConfigTree, node: NodeId) {
         self.current_node = node;
 
-        if let Some(children) = &node.children {
-            self.group_items = children.iter().collect();
-
-            let items_for_state = children
-                .iter()
-                .map(|e| e.name.as_str())
-                .collect::<Vec<&str>>();
-
-            self.group_items_state = StatefulList::with_items(items_for_state);
+        if let Some(children) = tree.children(node) {
+            self.group_items_state = StatefulList::with_items(children.to_vec());
 
             // set the first element of the state as selected
-            self.group_items_state.next();
-
-            // set description to that of the first element of the group
-            self.update_description();
+            if !children.is_empty() {
+                self.group_items_state.next();
+            }
         } else {
-            self.current_node = self.root_node;
+            self.current_node = tree.root();
             self.group_items_state = StatefulList::new();
         }
     }
 
-    pub fn get_selected_node(&self) -> Option<&'static ConfigNode> {
+    pub fn get_selected_id(&self) -> Option<NodeId> {
         let selected_i = self.group_items_state.state.selected()?;
-        Some(self.group_items.get(selected_i)?)
+        self.group_items_state.items.get(selected_i).copied()
     }
 
-    pub fn update_description(&mut self) -> Option<()> {
-        let selected_node = self.get_selected_node()?;
-        self.description = &selected_node.description;
+    pub fn get_selected_node<'t>(&self, tree: &'t ConfigTree) -> Option<&'t ConfigNode> {
+        Some(tree.node(self.get_selected_id()?))
+    }
 
-        Some(())
+    /// The description of the selected node, or of the current group if it is empty.
+    pub fn description<'t>(&self, tree: &'t ConfigTree) -> &'t str {
+        let node = self
+            .get_selected_node(tree)
+            .unwrap_or_else(|| tree.node(self.current_node));
+        &node.description
+    }
+
+    /// Selects the child of the current group whose key path is `key_path`, if there is one.
+    pub fn select_key_path(&mut self, tree: &ConfigTree, key_path: &str) {
+        let position = self
+            .group_items_state
+            .items
+            .iter()
+            .position(|&e| tree.node(e).key_path == key_path);
+
+        if position.is_some() {
+            self.group_items_state.state.select(position);
+        }
     }
 
-    pub fn enter_selected_node(&mut self) -> Option<()> {
-        let selected_node = self.get_selected_node()?;
+    pub fn enter_selected_node(&mut self, tree: &ConfigTree) -> Option<()> {
+        let selected_node = self.get_selected_id()?;
 
-        if !selected_node.is_leaf() {
+        if tree.is_group(selected_node) {
             self.breadcrumbs.push(self.current_node);
-            self.set_config_for_node(selected_node);
+            self.set_config_for_node(tree, selected_node);
         }
 
         Some(())
     }
 
-    pub fn exit_current_node(&mut self) -> Option<()> {
+    /// Shows `new_tree` instead of `old_tree`. The group that was open, the selected node and
+    /// the node of the open popup are restored, matching them by key path, as far as they
+    /// still exist in the new tree.
+    pub fn reload(&mut self, old_tree: &ConfigTree, new_tree: &ConfigTree) {
+        let key_path_of = |id: NodeId| old_tree.node(id).key_path.as_str();
+        let remap = |id: NodeId| new_tree.find(key_path_of(id));
+
+        // if the open group is gone then its closest ancestor which still exists is opened
+        let open_group = self
+            .breadcrumbs
+            .iter()
+            .chain(std::iter::once(&self.current_node))
+            .rev()
+            .filter_map(|&e| remap(e))
+            .find(|&e| new_tree.is_group(e))
+            .unwrap_or_else(|| new_tree.root());
+        let selected = self.get_selected_id().and_then(remap);
+        // the generators still running belong to the old tree
+        self.generations.clear();
+
+        self.breadcrumbs = new_tree.ancestors(open_group);
+        self.set_config_for_node(new_tree, open_group);
+
+        if let Some(idx) = selected.and_then(|selected| {
+            self.group_items_state
+                .items
+                .iter()
+                .position(|&e| e == selected)
+        }) {
+            self.group_items_state.state.select(Some(idx));
+        }
+
+        let remap_command = |id| remap(id).filter(|&e| !new_tree.is_group(e));
+        self.input_form = self.input_form.take().and_then(|mut form| {
+            form.node = remap_command(form.node)?;
+            Some(form)
+        });
+        self.confirm_popup = self.confirm_popup.take().and_then(|mut popup| {
+            popup.node = remap_command(popup.node)?;
+            Some(popup)
+        });
+    }
+
+    pub fn exit_current_node(&mut self, tree: &ConfigTree) -> Option<()> {
         let previous_node;
         if !self.breadcrumbs.is_empty() {
             previous_node = self.breadcrumbs.pop()?;
-            self.set_config_for_node(previous_node);
+            self.set_config_for_node(tree, previous_node);
         }
 
         Some(())
     }
 }
 
-impl<'a> fmt::Debug for UiState {
+impl fmt::Debug for UiState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("UiState")
-            .field("title", &self.title)
-            .field("description", &self.description)
             .field("breadcrumbs", &self.breadcrumbs)
             .field("current_node", &self.current_node)
             .field("command_output", &self.command_output)
@@ -107,6 +186,99 @@ impl<'a> fmt::Debug for UiState {
             )
             .field("group_items_state_list", &self.group_items_state.items)
             .field("need_redraw", &self.need_redraw)
+            .field("input_form_open", &self.input_form.is_some())
+            .field("confirm_popup_open", &self.confirm_popup.is_some())
+            .field("status", &self.status)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(key_path: &str, children: Vec<ConfigNode>) -> ConfigNode {
+        ConfigNode {
+            key_path: key_path.to_string(),
+            name: key_path.rsplit('.').next().unwrap().to_string(),
+            children: if children.is_empty() {
+                None
+            } else {
+                Some(children)
+            },
+            ..Default::default()
+        }
+    }
+
+    fn tree(children: Vec<ConfigNode>) -> ConfigTree {
+        ConfigTree::new(node("", children))
+    }
+
+    #[test]
+    fn test_navigation() {
+        let tree = tree(vec![
+            node("a", vec![]),
+            node("b", vec![node("b.c", vec![])]),
+        ]);
+        let mut state = UiState::new(&tree);
+        assert_eq!(state.get_selected_node(&tree).unwrap().key_path, "a");
+
+        // commands can't be entered
+        state.enter_selected_node(&tree);
+        assert_eq!(state.current_node, tree.root());
+
+        state.group_items_state.next();
+        state.enter_selected_node(&tree);
+        assert_eq!(state.current_node, tree.find("b").unwrap());
+        assert_eq!(state.breadcrumbs, vec![tree.root()]);
+        assert_eq!(state.get_selected_node(&tree).unwrap().key_path, "b.c");
+
+        state.exit_current_node(&tree);
+        assert_eq!(state.current_node, tree.root());
+        assert!(state.breadcrumbs.is_empty());
+    }
+
+    #[test]
+    fn test_reload_restores_the_open_group_and_the_selection() {
+        let children = || {
+            vec![
+                node("a", vec![]),
+                node(
+                    "b",
+                    vec![node(
+                        "b.c",
+                        vec![node("b.c.x", vec![]), node("b.c.y", vec![])],
+                    )],
+                ),
+            ]
+        };
+        let old_tree = tree(children());
+        let mut state = UiState::new(&old_tree);
+        state.group_items_state.state.select(Some(1));
+        state.enter_selected_node(&old_tree);
+        state.enter_selected_node(&old_tree);
+        state.group_items_state.state.select(Some(1));
+
+        let mut new_children = children();
+        new_children.insert(0, node("0", vec![]));
+        let new_tree = tree(new_children);
+        state.reload(&old_tree, &new_tree);
+
+        assert_eq!(state.current_node, new_tree.find("b.c").unwrap());
+        assert_eq!(state.breadcrumbs.len(), 2);
+        assert_eq!(
+            state.get_selected_node(&new_tree).unwrap().key_path,
+            "b.c.y"
+        );
+
+        // the group doesn't exist anymore, so its closest existing ancestor is opened
+        let newer_tree = tree(vec![node("b", vec![node("b.z", vec![])])]);
+        state.reload(&new_tree, &newer_tree);
+
+        assert_eq!(state.current_node, newer_tree.find("b").unwrap());
+        assert_eq!(
+            state.get_selected_node(&newer_tree).unwrap().key_path,
+            "b.z"
+        );
+    }
+}